@@ -0,0 +1,74 @@
+//! # artifacts
+//!
+//! Dist archives of tagged plugin releases. Archives are stored as
+//! `<artifacts_dir>/<package_name>/<name>-<version>.zip` together with a
+//! SHA-256 checksum file in `sha256sum` format.
+
+use sha2::{Digest, Sha256};
+
+use std::{
+    fs::{create_dir_all, write, File},
+    io,
+    path::{Path, PathBuf}
+};
+
+use error::Result;
+use git::VcsBackend;
+use wordpress::Plugin;
+
+/// Extension of checksum files stored next to archives.
+pub const CHECKSUM_EXTENSION: &str = "sha256";
+
+/// Get the path of the dist archive of a plugin release, relative to the
+/// artifacts directory.
+pub fn get_archive_path(package_name: &str, tag: &str) -> PathBuf {
+    let name = package_name.rsplit('/').next().unwrap_or(package_name);
+
+    PathBuf::from(package_name).join(format!("{}-{}.zip", name, tag))
+}
+
+/// Get the path of the checksum file of a dist archive.
+pub fn get_checksum_path(archive: &Path) -> PathBuf {
+    let mut file_name = archive.file_name().unwrap_or_default().to_os_string();
+
+    file_name.push(".");
+    file_name.push(CHECKSUM_EXTENSION);
+
+    archive.with_file_name(file_name)
+}
+
+/// Get the SHA-256 checksum of a file as a hex string.
+pub fn sha256_file(file: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+
+    io::copy(&mut File::open(file)?, &mut hasher)?;
+
+    let checksum: Vec<String> = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+
+    Ok(checksum.join(""))
+}
+
+/// Build the dist archive of a tagged plugin release from the plugin git
+/// repository, unless it exists already. Returns the absolute path to the
+/// archive.
+pub fn build_dist_archive(git: &dyn VcsBackend, plugin: &Plugin, tag: &str, artifacts_dir: &Path) -> Result<PathBuf> {
+    let archive = artifacts_dir.join(get_archive_path(&plugin.package_name, tag));
+    let checksum_file = get_checksum_path(&archive);
+
+    if archive.exists() && checksum_file.exists() {
+        return Ok(archive);
+    }
+
+    if let Some(parent) = archive.parent() {
+        create_dir_all(parent)?;
+    }
+
+    git.archive_zip(tag, &plugin.get_cli_name()?, &archive)?;
+
+    let checksum = sha256_file(&archive)?;
+    let file_name = archive.file_name().unwrap_or_default().to_string_lossy().to_string();
+
+    write(&checksum_file, format!("{}  {}\n", checksum, file_name))?;
+
+    Ok(archive)
+}
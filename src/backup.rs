@@ -0,0 +1,394 @@
+//! # backup
+//!
+//! Backups of plugin directories. Every backup is a timestamped tar.gz archive
+//! of the whole plugin directory, `.git` included, stored as
+//! `<backups_dir>/<slug>/<slug>-<timestamp>.tar.gz`. An `index.json` in the
+//! backups directory records the plugin version and contents of each archive.
+
+use chrono::{SecondsFormat, Utc};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde_json;
+use tar::{Archive, Builder};
+
+use std::{
+    fs::{create_dir_all, read_to_string, remove_dir_all, remove_file, rename, write, File},
+    path::{Path, PathBuf}
+};
+
+use artifacts::sha256_file;
+use error::{Error, Result};
+use wordpress::Plugin;
+
+/// Name of the backup index file inside the backups directory.
+pub const INDEX_FILE_NAME: &str = "index.json";
+
+/// Details of a single plugin backup.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackupEntry {
+    /// Unique identifier of the backup, the archive file name without
+    /// extension.
+    pub id: String,
+    /// Slug of the plugin the backup was made from.
+    pub plugin: String,
+    pub package_name: String,
+    /// Installed plugin version at the time of the backup.
+    pub version: Option<String>,
+    /// Git commit the plugin directory was at.
+    pub commit: Option<String>,
+    /// Creation time in RFC 3339 format.
+    pub created: String,
+    /// Archive path relative to the backups directory.
+    pub file: String,
+    /// Number of files in the archive.
+    pub files: usize,
+    /// Archive size in bytes.
+    pub size: u64,
+    /// SHA-256 checksum of the archive.
+    pub sha256: String,
+}
+
+/// Contents of the backup index file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BackupIndex {
+    backups: Vec<BackupEntry>,
+}
+
+/// Backups of all managed plugins inside a backups directory.
+pub struct BackupStore {
+    dir: PathBuf,
+}
+
+impl BackupStore {
+    /// Get a backup store for a backups directory.
+    pub fn new(dir: PathBuf) -> Self {
+        BackupStore { dir }
+    }
+
+    /// Get the path of the backup index file.
+    pub fn get_index_path(&self) -> PathBuf {
+        self.dir.join(INDEX_FILE_NAME)
+    }
+
+    /// Get the absolute path of a backup archive.
+    pub fn get_archive_path(&self, entry: &BackupEntry) -> PathBuf {
+        self.dir.join(&entry.file)
+    }
+
+    /// Get all backups, oldest first. A missing index means there are no
+    /// backups.
+    pub fn load_index(&self) -> Result<Vec<BackupEntry>> {
+        let index_path = self.get_index_path();
+
+        if !index_path.exists() {
+            return Ok(Vec::new());
+        }
+
+        match serde_json::from_str::<BackupIndex>(&read_to_string(&index_path)?) {
+            Ok(index) => Ok(index.backups),
+            Err(e) => Err(Error::Backup(format!(
+                "Backup index `{}` is invalid: {}",
+                index_path.display(),
+                e
+            )))
+        }
+    }
+
+    /// Write the backup index, replacing the previous one atomically.
+    fn save_index(&self, backups: Vec<BackupEntry>) -> Result<()> {
+        let index = BackupIndex { backups };
+        let contents = match serde_json::to_string_pretty(&index) {
+            Ok(c) => c,
+            Err(e) => return Err(Error::Backup(format!("Cannot write backup index: {}", e)))
+        };
+
+        let index_path = self.get_index_path();
+        let tmp_path = index_path.with_extension("json.tmp");
+
+        write(&tmp_path, format!("{}\n", contents))?;
+        rename(&tmp_path, &index_path)?;
+
+        Ok(())
+    }
+
+    /// Get the backups of a single plugin, oldest first.
+    pub fn get_plugin_backups(&self, slug: &str) -> Result<Vec<BackupEntry>> {
+        Ok(self.load_index()?.into_iter().filter(|e| e.plugin == slug).collect())
+    }
+
+    /// Create a backup of the whole plugin directory and add it to the index.
+    pub fn create(&self, plugin: &Plugin, commit: Option<String>) -> Result<BackupEntry> {
+        let slug = plugin.get_cli_name()?;
+        let plugin_dir = plugin.get_plugin_dir();
+        let now = Utc::now();
+
+        let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let mut id = format!("{}-{}", slug, timestamp);
+        let mut counter = 1;
+
+        while self.dir.join(&slug).join(format!("{}.tar.gz", id)).exists() {
+            id = format!("{}-{}-{}", slug, timestamp, counter);
+            counter += 1;
+        }
+
+        let file = format!("{}/{}.tar.gz", slug, id);
+        let archive = self.dir.join(&file);
+
+        create_dir_all(self.dir.join(&slug))?;
+
+        let files = match write_archive(&plugin_dir, &slug, &archive) {
+            Ok(f) => f,
+            Err(e) => {
+                let _ = remove_file(&archive);
+
+                return Err(Error::Backup(format!(
+                    "Creating backup failed for plugin `{}`: {}",
+                    plugin.get_nicename(),
+                    e
+                )));
+            }
+        };
+
+        let entry = BackupEntry {
+            id,
+            plugin: slug,
+            package_name: plugin.package_name.clone(),
+            version: plugin.installed_version.clone(),
+            commit,
+            created: now.to_rfc3339_opts(SecondsFormat::Secs, true),
+            file,
+            files,
+            size: archive.metadata()?.len(),
+            sha256: sha256_file(&archive)?,
+        };
+
+        let mut backups = self.load_index()?;
+        backups.push(entry.clone());
+        self.save_index(backups)?;
+
+        Ok(entry)
+    }
+
+    /// Get the oldest backups of a plugin which exceed the `keep` limit. At
+    /// least one backup is always kept.
+    pub fn get_prunable(&self, slug: &str, keep: usize) -> Result<Vec<BackupEntry>> {
+        let backups = self.get_plugin_backups(slug)?;
+        let remove_count = backups.len().saturating_sub(keep.max(1));
+
+        Ok(backups.into_iter().take(remove_count).collect())
+    }
+
+    /// Remove the oldest backups of a plugin so that at most `keep` remain.
+    /// At least one backup is always kept. Returns the removed backups.
+    pub fn prune(&self, slug: &str, keep: usize) -> Result<Vec<BackupEntry>> {
+        let removed = self.get_prunable(slug, keep)?;
+
+        if removed.is_empty() {
+            return Ok(removed);
+        }
+
+        let kept: Vec<BackupEntry> = self
+            .load_index()?
+            .into_iter()
+            .filter(|e| !removed.contains(e))
+            .collect();
+
+        self.save_index(kept)?;
+
+        for entry in &removed {
+            let archive = self.get_archive_path(entry);
+
+            if archive.exists() {
+                remove_file(archive)?;
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Check that a backup archive exists and matches its recorded checksum.
+    pub fn verify(&self, entry: &BackupEntry) -> Result<()> {
+        let archive = self.get_archive_path(entry);
+
+        if !archive.is_file() {
+            return Err(Error::Backup(format!(
+                "Backup archive `{}` does not exist",
+                archive.display()
+            )));
+        }
+
+        if sha256_file(&archive)? != entry.sha256 {
+            return Err(Error::Backup(format!(
+                "Backup archive `{}` does not match its checksum",
+                archive.display()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Get the directory a backup is extracted into before it replaces the
+    /// plugin directory.
+    fn get_staging_dir(&self, entry: &BackupEntry, plugin_dir: &Path) -> Result<PathBuf> {
+        Ok(get_parent_dir(plugin_dir)?.join(format!(".wppr-restore-{}", entry.plugin)))
+    }
+
+    /// Extract a backup next to the plugin directory without touching the
+    /// plugin directory itself. Returns the extracted plugin directory.
+    pub fn extract(&self, entry: &BackupEntry, plugin_dir: &Path) -> Result<PathBuf> {
+        self.verify(entry)?;
+
+        let staging_dir = self.get_staging_dir(entry, plugin_dir)?;
+
+        if staging_dir.exists() {
+            remove_dir_all(&staging_dir)?;
+        }
+
+        create_dir_all(&staging_dir)?;
+
+        if let Err(e) = self.open_archive(entry)?.unpack(&staging_dir) {
+            let _ = remove_dir_all(&staging_dir);
+
+            return Err(Error::Backup(format!(
+                "Extracting backup `{}` failed: {}",
+                entry.id,
+                e
+            )));
+        }
+
+        let extracted = staging_dir.join(&entry.plugin);
+
+        if !extracted.is_dir() {
+            let _ = remove_dir_all(&staging_dir);
+
+            return Err(Error::Backup(format!(
+                "Backup `{}` does not contain plugin directory `{}`",
+                entry.id,
+                entry.plugin
+            )));
+        }
+
+        Ok(extracted)
+    }
+
+    /// Remove an extracted backup which will not be restored.
+    pub fn discard_extracted(&self, entry: &BackupEntry, plugin_dir: &Path) -> Result<()> {
+        let staging_dir = self.get_staging_dir(entry, plugin_dir)?;
+
+        if staging_dir.exists() {
+            remove_dir_all(&staging_dir)?;
+        }
+
+        Ok(())
+    }
+
+    /// Replace the plugin directory with an extracted backup.
+    pub fn replace_plugin_dir(&self, entry: &BackupEntry, plugin_dir: &Path) -> Result<()> {
+        let staging_dir = self.get_staging_dir(entry, plugin_dir)?;
+        let old_dir = get_parent_dir(plugin_dir)?.join(format!(".wppr-old-{}", entry.plugin));
+
+        if old_dir.exists() {
+            remove_dir_all(&old_dir)?;
+        }
+
+        if plugin_dir.exists() {
+            rename(plugin_dir, &old_dir)?;
+        }
+
+        if let Err(e) = rename(staging_dir.join(&entry.plugin), plugin_dir) {
+            if old_dir.exists() {
+                rename(&old_dir, plugin_dir)?;
+            }
+
+            return Err(Error::Io(e));
+        }
+
+        remove_dir_all(&staging_dir)?;
+
+        if old_dir.exists() {
+            remove_dir_all(&old_dir)?;
+        }
+
+        Ok(())
+    }
+
+    /// Restore a whole plugin directory from a backup. The archive is
+    /// extracted next to the plugin directory first and only swapped in once
+    /// extraction has succeeded.
+    pub fn restore(&self, entry: &BackupEntry, plugin_dir: &Path) -> Result<()> {
+        self.extract(entry, plugin_dir)?;
+        self.replace_plugin_dir(entry, plugin_dir)
+    }
+
+    /// Restore only the `.git` directory of a plugin from a backup, keeping
+    /// all other plugin files as they are.
+    pub fn restore_git_dir(&self, entry: &BackupEntry, plugin_dir: &Path) -> Result<()> {
+        self.verify(entry)?;
+
+        let parent = get_parent_dir(plugin_dir)?;
+        let git_dir = plugin_dir.join(".git");
+        let git_prefix = Path::new(&entry.plugin).join(".git");
+
+        if git_dir.exists() {
+            remove_dir_all(&git_dir)?;
+        }
+
+        let mut archive = self.open_archive(entry)?;
+
+        for archive_entry in archive.entries()? {
+            let mut archive_entry = archive_entry?;
+
+            if archive_entry.path()?.starts_with(&git_prefix) {
+                archive_entry.unpack_in(parent)?;
+            }
+        }
+
+        if !git_dir.is_dir() {
+            return Err(Error::Backup(format!(
+                "Backup `{}` does not contain a .git directory",
+                entry.id
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Open a backup archive for reading.
+    fn open_archive(&self, entry: &BackupEntry) -> Result<Archive<GzDecoder<File>>> {
+        let file = File::open(self.get_archive_path(entry))?;
+
+        Ok(Archive::new(GzDecoder::new(file)))
+    }
+}
+
+/// Write a tar.gz archive of a directory with all paths prefixed by `prefix`.
+/// Returns the number of files in the archive.
+fn write_archive(dir: &Path, prefix: &str, archive: &Path) -> Result<usize> {
+    let encoder = GzEncoder::new(File::create(archive)?, Compression::default());
+    let mut builder = Builder::new(encoder);
+
+    builder.follow_symlinks(false);
+    builder.append_dir_all(prefix, dir)?;
+
+    builder.into_inner()?.finish()?;
+
+    let mut files = 0;
+
+    for entry in Archive::new(GzDecoder::new(File::open(archive)?)).entries()? {
+        if entry?.header().entry_type().is_file() {
+            files += 1;
+        }
+    }
+
+    Ok(files)
+}
+
+/// Get the directory a plugin directory resides in.
+fn get_parent_dir(plugin_dir: &Path) -> Result<&Path> {
+    match plugin_dir.parent() {
+        Some(p) => Ok(p),
+        None => Err(Error::Backup(format!(
+            "Cannot restore backup into `{}`",
+            plugin_dir.display()
+        )))
+    }
+}
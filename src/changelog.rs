@@ -0,0 +1,130 @@
+//! # changelog
+//!
+//! Reading release notes of plugin versions from changelog files shipped with
+//! plugins.
+
+use std::{fs::read_to_string, path::Path, sync::OnceLock};
+
+use regex::{self, Regex};
+
+/// Files which are searched for changelogs, in order.
+const CHANGELOG_FILES: [&str; 6] = [
+    "CHANGELOG.md",
+    "changelog.md",
+    "changelog.txt",
+    "CHANGELOG.txt",
+    "readme.txt",
+    "README.txt",
+];
+
+/// A Markdown (`## 1.2.3`) or WordPress readme (`= 1.2.3 =`) heading.
+struct Heading {
+    /// Nesting depth of the heading, deeper headings have larger values.
+    depth: usize,
+    text: String,
+}
+
+/// Get the matcher of heading lines, which is compiled once as headings are
+/// matched line by line.
+fn get_heading_matcher() -> &'static Regex {
+    static MATCHER: OnceLock<Regex> = OnceLock::new();
+
+    MATCHER.get_or_init(|| {
+        Regex::new(r"^\s*(#{1,6}|={1,3})\s*(.*?)\s*[#=]*\s*$").unwrap()
+    })
+}
+
+/// Parse a heading line. WordPress readme headings nest the other way around
+/// compared to Markdown, `=== Title ===` being the topmost heading.
+fn parse_heading(line: &str) -> Option<Heading> {
+    let captures = get_heading_matcher().captures(line)?;
+    let markup = captures.get(1)?.as_str();
+    let text = captures.get(2)?.as_str();
+
+    if text.is_empty() {
+        return None;
+    }
+
+    let depth = match markup.starts_with('#') {
+        true => markup.len(),
+        false => 4 - markup.len()
+    };
+
+    Some(Heading {
+        depth,
+        text: text.to_string()
+    })
+}
+
+/// Get the release notes of a version from changelog contents. The notes are
+/// the lines below the heading of the version, up to the next heading which
+/// is not nested below it.
+///
+/// # Example
+///
+/// ```
+/// use wppr::changelog::get_version_section;
+///
+/// let readme = "== Changelog ==\n\n= 1.2.0 - 2024-01-01 =\n* New feature\n\n= 1.1.0 =\n* Fix\n";
+/// let changelog = "## [1.2.0]\n### Added\n- Feature\n\n## [1.1.0]\n- Fix\n";
+///
+/// assert_eq!(Some("* New feature".to_string()), get_version_section(readme, "1.2.0"));
+/// assert_eq!(Some("### Added\n- Feature".to_string()), get_version_section(changelog, "1.2.0"));
+/// assert_eq!(None, get_version_section(changelog, "1.2"));
+/// ```
+pub fn get_version_section(contents: &str, version: &str) -> Option<String> {
+    let pattern = format!(r"(?i)^(?:version\s+)?\[?v?{}\]?(?:$|[^0-9A-Za-z.])", regex::escape(version));
+    let matcher = Regex::new(&pattern).unwrap();
+
+    let mut lines = contents.lines();
+    let mut section_depth = None;
+
+    for line in lines.by_ref() {
+        if let Some(heading) = parse_heading(line) {
+            if matcher.is_match(&heading.text) {
+                section_depth = Some(heading.depth);
+                break;
+            }
+        }
+    }
+
+    let depth = section_depth?;
+
+    let section: Vec<&str> = lines
+        .take_while(|l| parse_heading(l).map(|h| h.depth > depth).unwrap_or(true))
+        .collect();
+
+    let section = section.join("\n").trim().to_string();
+
+    match section.is_empty() {
+        true => None,
+        false => Some(section)
+    }
+}
+
+/// Find the release notes of a version from the changelog files of a plugin.
+/// Readme files are only searched below their `Changelog` heading.
+pub fn find_version_changelog(plugin_dir: &Path, version: &str) -> Option<String> {
+    let readme_changelog = Regex::new(r"(?im)^\s*==\s*changelog\s*==").unwrap();
+
+    for file in CHANGELOG_FILES.iter() {
+        let contents = match read_to_string(plugin_dir.join(file)) {
+            Ok(c) => c,
+            Err(_) => continue
+        };
+
+        let contents = match file.to_lowercase().starts_with("readme") {
+            true => match readme_changelog.find(&contents) {
+                Some(m) => contents[m.start()..].to_string(),
+                None => continue
+            },
+            false => contents
+        };
+
+        if let Some(section) = get_version_section(&contents, version) {
+            return Some(section);
+        }
+    }
+
+    None
+}
@@ -2,20 +2,121 @@
 //!
 //! All command implementations.
 
+use serde_json;
+use toml;
+
 use std::{
-    fs::create_dir,
-    path::PathBuf
+    collections::hash_map::RandomState,
+    env,
+    fs::{create_dir, create_dir_all, read_to_string, remove_dir_all, remove_file, rename, write, OpenOptions},
+    hash::{BuildHasher, Hasher},
+    io::{self, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    process
 };
 
-use config::RuntimeConfig;
-use pipeline::Pipeline;
+use backup::BackupStore;
+use composer::is_generated_composerjson;
+use config_editor::ConfigEditor;
+use config::{find_wordpress_root, HooksConfig, PluginConfig, PluginHold, RuntimeConfig};
+use error::{ConfigError, Error, Result};
+use git::{get_backend, VcsBackend};
+use mirror::{sync_directory, MIRROR_FILES};
+use pipeline::{restore_plugin_backup, Pipeline, UpgradeOutcome};
 use prettytable::Table;
-use wordpress::Plugin;
+use repository::{add_dist_entries, build_packages_json, get_package_versions, PACKAGES_FILE_NAME};
+use server::RepositoryServer;
+use shell::{Shell, DEFAULT_TIMEOUT};
+use version::Version;
+use wordpress::{InstalledPlugin, Plugin, PluginHeader, WpCli};
+
+/// How many file names are tried when creating the run summary file.
+const MAX_SUMMARY_FILE_ATTEMPTS: usize = 10;
+
+/// Options for the `init` command.
+#[derive(Debug, Clone)]
+pub struct InitOptions {
+    pub wpcli: String,
+    pub vendor: String,
+    pub remote_template: String,
+    pub plugins: Vec<String>,
+    pub non_wporg_only: bool,
+    pub output: PathBuf,
+    pub force: bool,
+    pub dry_run: bool,
+}
+
+/// Summary of a `run` command, given to post-upgrade hooks.
+#[derive(Debug, Default, Serialize)]
+pub struct RunSummary {
+    pub upgraded: Vec<UpgradedPlugin>,
+    pub skipped: Vec<String>,
+    pub failed: Vec<FailedPlugin>,
+}
+
+/// A plugin which was upgraded during a run.
+#[derive(Debug, Serialize)]
+pub struct UpgradedPlugin {
+    pub plugin: String,
+    pub package_name: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// A plugin which failed to upgrade during a run.
+#[derive(Debug, Serialize)]
+pub struct FailedPlugin {
+    pub plugin: String,
+    pub package_name: String,
+    pub error: String,
+}
+
+impl RunSummary {
+    /// Get environment variables which describe this summary.
+    pub fn get_env_vars(&self) -> Vec<(&'static str, String)> {
+        let upgraded: Vec<String> = self.upgraded.iter().map(|p| p.plugin.clone()).collect();
+        let failed: Vec<String> = self.failed.iter().map(|p| p.plugin.clone()).collect();
+
+        vec![
+            ("WPPR_UPGRADED", upgraded.join(",")),
+            ("WPPR_SKIPPED", self.skipped.join(",")),
+            ("WPPR_FAILED", failed.join(",")),
+        ]
+    }
+}
+
+/// A managed plugin with an update available.
+#[derive(Debug, Serialize)]
+pub struct OutdatedPlugin {
+    pub plugin: String,
+    pub package_name: String,
+    pub current: String,
+    pub available: Option<String>,
+    /// What `run` would do with the update.
+    pub action: String,
+}
+
+impl OutdatedPlugin {
+    /// Would `run` upgrade the plugin? Held plugins, plugins blocked by the
+    /// upgrade policy, and plugins whose policy is invalid are not upgraded.
+    pub fn is_upgradable(&self) -> bool {
+        self.action == "upgrade"
+    }
+}
+
+/// The signature verification result of a plugin repository tag.
+#[derive(Debug)]
+pub struct TagVerification {
+    pub plugin: String,
+    pub tag: String,
+    /// Why the tag is not trusted, `None` if it is signed by a trusted key.
+    pub error: Option<String>,
+}
 
 /// Get all plugins which are being managed.
 pub fn get_managed_plugins(config: &RuntimeConfig) -> Vec<Plugin> {
     let mut plugins: Vec<Plugin> = Vec::new();
-    let cwd = config.cwd.clone();
 
     let plugins_being_managed: Vec<_> = config.plugins.clone();
 
@@ -24,16 +125,17 @@ pub fn get_managed_plugins(config: &RuntimeConfig) -> Vec<Plugin> {
     }
 
     for plugin_cfg in plugins_being_managed {
-        plugins.push(Plugin::from_config(plugin_cfg, &cwd));
+        let base_dir = config.get_index_path_base(&plugin_cfg);
+
+        plugins.push(Plugin::from_config(plugin_cfg, &base_dir));
     }
 
     plugins
 }
 
 /// Creates a directory for backups.
-fn maybe_create_backups_directory(config: &RuntimeConfig) -> Result<PathBuf, &'static str> {
-    let mut backup_dir: PathBuf = config.cwd.clone();
-    backup_dir.push(".wpprbackups");
+fn maybe_create_backups_directory(config: &RuntimeConfig) -> Result<PathBuf> {
+    let backup_dir: PathBuf = config.get_backups_dir();
 
     if backup_dir.exists() && backup_dir.is_dir() {
         return Ok(backup_dir);
@@ -45,11 +147,893 @@ fn maybe_create_backups_directory(config: &RuntimeConfig) -> Result<PathBuf, &'s
         return Ok(backup_dir);
     }
 
-    return Err("Could not create backups directory, do you have proper permissions?");
+    return Err(Error::Backup(
+        "Could not create backups directory, do you have proper permissions?".to_string()
+    ));
+}
+
+/// Run global hook commands inside the configuration directory.
+fn run_hooks(
+    config: &RuntimeConfig,
+    name: &str,
+    hooks: &HooksConfig,
+    env_vars: &[(&str, String)],
+) -> Result<()> {
+    if hooks.commands.is_empty() {
+        return Ok(());
+    }
+
+    println!("[{}] Running hook commands", name);
+
+    let mut shell = Shell::new(
+        config.binaries.get_shell(),
+        config.cwd.clone(),
+        hooks.timeout.unwrap_or(DEFAULT_TIMEOUT),
+    );
+
+    for (key, value) in env_vars {
+        shell.set_env(key, value);
+    }
+
+    for cmd in &hooks.commands {
+        println!("[{}] Running command `{}`", name, cmd);
+
+        if config.dry_run {
+            continue;
+        }
+
+        let output = shell.run(cmd)?;
+
+        for line in output.stdout.lines() {
+            println!("[{}] stdout: {}", name, line);
+        }
+
+        for line in output.stderr.lines() {
+            println!("[{}] stderr: {}", name, line);
+        }
+
+        if let Some(e) = output.error() {
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Write the run summary into a new file in the temp directory. The file name
+/// has a random suffix and the file is created exclusively, so a file or
+/// symlink planted in a shared temp directory is never written through.
+fn create_summary_file(contents: &str) -> Result<PathBuf> {
+    for _ in 0..MAX_SUMMARY_FILE_ATTEMPTS {
+        let suffix = RandomState::new().build_hasher().finish();
+        let path = env::temp_dir().join(format!("wppr-run-summary-{}-{:016x}.json", process::id(), suffix));
+
+        let mut file = match OpenOptions::new().write(true).create_new(true).mode(0o600).open(&path) {
+            Ok(f) => f,
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(Error::Io(e))
+        };
+
+        file.write_all(contents.as_bytes())?;
+
+        return Ok(path);
+    }
+
+    Err(Error::Io(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "Could not create a unique run summary file"
+    )))
+}
+
+/// Run post-upgrade hooks, exposing the run summary to them as environment
+/// variables and as a JSON file.
+fn run_post_upgrade_hooks(config: &RuntimeConfig, summary: &RunSummary) -> Result<()> {
+    if config.post_upgrade.commands.is_empty() {
+        return Ok(());
+    }
+
+    let summary_json = serde_json::to_string_pretty(summary)?;
+    let summary_file = create_summary_file(&summary_json)?;
+
+    let mut env_vars = summary.get_env_vars();
+    env_vars.push(("WPPR_SUMMARY_FILE", summary_file.to_string_lossy().to_string()));
+
+    let result = run_hooks(config, "post_upgrade", &config.post_upgrade, &env_vars);
+
+    let _ = remove_file(&summary_file);
+
+    result
+}
+
+/// Sanitize a string to be usable as a Composer vendor or package name part.
+pub fn sanitize_package_name_part(name: &str) -> String {
+    let lowercased = name.to_lowercase();
+    let parts: Vec<&str> = lowercased
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|p| !p.is_empty())
+        .collect();
+
+    parts.join("-")
+}
+
+/// Generate plugin configuration for an installed plugin. The index path is
+/// made relative to `base_dir` when possible.
+pub fn generate_plugin_config(
+    installed: &InstalledPlugin,
+    plugins_dir: &Path,
+    base_dir: &Path,
+    vendor: &str,
+    remote_template: &str,
+) -> PluginConfig {
+    let index_path = plugins_dir.join(&installed.file);
+    let relative_index_path = index_path.strip_prefix(base_dir).unwrap_or(&index_path);
+
+    let vendor_name = sanitize_package_name_part(vendor);
+    let package_slug = sanitize_package_name_part(&installed.name);
+
+    PluginConfig {
+        index_path: relative_index_path.to_string_lossy().to_string(),
+        package_name: format!("{}/{}", vendor_name, package_slug),
+        remote_repository: remote_template
+            .replace("{vendor}", &vendor_name)
+            .replace("{slug}", &installed.name),
+        pre_cmds: None,
+        post_cmds: None,
+        cmds_timeout: None,
+        composer: None,
+        composer_strategy: None,
+        hold: None,
+        allow: None,
+        max_version: None,
+    }
+}
+
+/// Render a plugin configuration as a TOML `[[plugins]]` section.
+fn render_plugin_config(plugin_config: &PluginConfig) -> String {
+    format!(
+        "[[plugins]]\nindex_path = {}\npackage_name = {}\nremote_repository = {}\n",
+        toml::Value::String(plugin_config.index_path.clone()),
+        toml::Value::String(plugin_config.package_name.clone()),
+        toml::Value::String(plugin_config.remote_repository.clone())
+    )
+}
+
+/// Scaffolds a new configuration file from plugins installed in WordPress.
+pub fn init(options: InitOptions) -> Result<bool> {
+    if options.output.exists() && !options.force && !options.dry_run {
+        return Err(Error::Config(
+            ConfigError::new("Configuration file already exists, use `--force` to overwrite it")
+                .with_file(&options.output)
+        ));
+    }
+
+    let config_dir: PathBuf = match options.output.parent() {
+        Some(dir) => dir.to_path_buf(),
+        None => env::current_dir()?
+    };
+
+    let wp_root = find_wordpress_root(&config_dir);
+    let base_dir = wp_root.clone().unwrap_or_else(|| config_dir.clone());
+
+    let wp_cli = WpCli::new(options.wpcli.clone(), base_dir.clone());
+    let plugins_dir = wp_cli.get_plugins_dir()?;
+    let installed_plugins = wp_cli.list_plugins(options.non_wporg_only)?;
+
+    let mut contents: String = format!(
+        "# WPPR configuration generated with `wppr init`\n\
+         \n\
+         [binaries]\n\
+         git = \"git\"\n\
+         wpcli = {}\n\
+         \n\
+         [git]\n\
+         user_name = \"WPPR\"\n\
+         user_email = \"wppr@example.com\"\n\
+         force_push = false\n",
+        toml::Value::String(options.wpcli.clone())
+    );
+
+    let mut plugin_count = 0;
+
+    for installed in installed_plugins {
+        if !options.plugins.is_empty() && !options.plugins.contains(&installed.name) {
+            continue;
+        }
+
+        if options.non_wporg_only && installed.is_on_wporg() {
+            continue;
+        }
+
+        if !installed.has_plugin_dir() {
+            eprintln!("Skipping plugin `{}`, it does not reside in its own directory", installed.name);
+            continue;
+        }
+
+        let plugin_config = generate_plugin_config(
+            &installed,
+            &plugins_dir,
+            &base_dir,
+            &options.vendor,
+            &options.remote_template,
+        );
+
+        println!("Adding plugin `{}` as `{}`", installed.file, plugin_config.package_name);
+
+        contents.push('\n');
+        contents.push_str(&render_plugin_config(&plugin_config));
+
+        plugin_count += 1;
+    }
+
+    if plugin_count < 1 {
+        println!("No matching plugins found");
+    }
+
+    if options.dry_run {
+        println!("\n{}", contents);
+
+        return Ok(true);
+    }
+
+    write(&options.output, contents)?;
+
+    println!("Configuration written to `{}`", options.output.display());
+
+    Ok(true)
+}
+
+/// Get the configuration file of a runtime configuration, which is required
+/// for commands that edit the configuration.
+fn get_config_file(config: &RuntimeConfig) -> Result<PathBuf> {
+    match config.file {
+        Some(ref f) => Ok(f.clone()),
+        None => Err(Error::Config(ConfigError::new("Configuration file is unknown")))
+    }
+}
+
+/// Write an edited configuration, or print it when running dry.
+fn save_config_editor(config: &RuntimeConfig, editor: &ConfigEditor) -> Result<()> {
+    if config.dry_run {
+        println!("{}", editor.to_toml_string());
+
+        return Ok(());
+    }
+
+    editor.save()
+}
+
+/// Adds an installed plugin to the configuration file.
+pub fn add(config: RuntimeConfig, slug: &str, vendor: &str, remote_template: &str) -> Result<bool> {
+    let config_file = get_config_file(&config)?;
+
+    if config.find_plugin_config(slug).is_some() {
+        return Err(Error::Plugin(format!("Plugin `{}` is already being managed", slug)));
+    }
+
+    let base_dir = config.wp_root.clone().unwrap_or_else(|| config.cwd.clone());
+    let wp_cli = WpCli::new(config.binaries.wpcli.clone(), base_dir.clone());
+
+    let installed = match wp_cli.list_plugins(false)?.into_iter().find(|p| p.name == slug) {
+        Some(p) => p,
+        None => return Err(Error::Plugin(format!("Plugin `{}` is not installed", slug)))
+    };
+
+    if !installed.has_plugin_dir() {
+        return Err(Error::Plugin(format!(
+            "Plugin `{}` does not reside in its own directory and cannot be managed",
+            slug
+        )));
+    }
+
+    let plugin_config = generate_plugin_config(
+        &installed,
+        &wp_cli.get_plugins_dir()?,
+        &base_dir,
+        vendor,
+        remote_template,
+    );
+
+    let plugin = Plugin::from_config(plugin_config.clone(), &config.get_index_path_base(&plugin_config));
+
+    if !plugin.is_valid() {
+        return Err(Error::Plugin(format!(
+            "Plugin `{}` is not valid for management",
+            plugin.get_nicename()
+        )));
+    }
+
+    let mut editor = ConfigEditor::open(&config_file)?;
+
+    editor.add_plugin(&plugin_config)?;
+    save_config_editor(&config, &editor)?;
+
+    println!("Added plugin `{}` as `{}`", plugin.get_nicename(), plugin_config.package_name);
+
+    Ok(true)
+}
+
+/// Removes a plugin from the configuration file, and optionally deletes the
+/// git repository and composer.json created for the plugin.
+pub fn remove(config: RuntimeConfig, slug: &str, purge: bool) -> Result<bool> {
+    let config_file = get_config_file(&config)?;
+
+    let plugin_config = match config.find_plugin_config(slug) {
+        Some(p) => p.clone(),
+        None => return Err(Error::Plugin(format!("Plugin `{}` is not being managed", slug)))
+    };
+
+    let mut editor = ConfigEditor::open(&config_file)?;
+
+    editor.remove_plugin(&plugin_config.index_path)?;
+    save_config_editor(&config, &editor)?;
+
+    println!("Removed plugin `{}`", plugin_config.index_path);
+
+    if !purge {
+        return Ok(true);
+    }
+
+    let plugin = Plugin::from_config(plugin_config.clone(), &config.get_index_path_base(&plugin_config));
+
+    // mirrors only contain wppr managed files
+    if let Some(mirror_dir) = config.get_mirror_dir(&plugin)? {
+        if mirror_dir.exists() {
+            println!("Deleting `{}`", mirror_dir.display());
+
+            if !config.dry_run {
+                remove_dir_all(&mirror_dir)?;
+            }
+        }
+
+        return Ok(true);
+    }
+
+    let git_dir = plugin.get_git_dir_path();
+
+    if git_dir.exists() {
+        println!("Deleting `{}`", git_dir.display());
+
+        if !config.dry_run {
+            remove_dir_all(&git_dir)?;
+        }
+    }
+
+    let composerjson_path = plugin.get_composerjson_path();
+    let is_generated = read_to_string(&composerjson_path)
+        .map(|c| is_generated_composerjson(&c, &plugin.package_name))
+        .unwrap_or(false);
+
+    if is_generated {
+        println!("Deleting `{}`", plugin.get_composerjson_path().display());
+
+        if !config.dry_run {
+            remove_file(plugin.get_composerjson_path())?;
+        }
+    }
+
+    Ok(true)
+}
+
+/// Exports managed plugins into a static Composer repository.
+pub fn export_repository(config: RuntimeConfig, dir: &Path) -> Result<bool> {
+    write_repository(&config, dir)?;
+
+    Ok(true)
+}
+
+/// Get the .git directory of a plugin repository, which is inside the
+/// mirror of the plugin if the plugin is mirrored.
+fn get_repository_git_dir(config: &RuntimeConfig, plugin: &Plugin) -> Result<PathBuf> {
+    let mirror_dir = match config.get_mirror_dir(plugin)? {
+        Some(d) => d,
+        None => return plugin.get_git_dir()
+    };
+
+    let git_dir = mirror_dir.join(".git");
+
+    if git_dir.is_dir() {
+        return Ok(git_dir);
+    }
+
+    Err(Error::Plugin(format!(
+        "Cannot fetch git directory for plugin `{}`, mirror `{}` is not a repository",
+        plugin.get_nicename(),
+        mirror_dir.display()
+    )))
+}
+
+/// Writes the static Composer repository of managed plugins into a
+/// directory. `packages.json` is replaced atomically, so it can be served
+/// while it is being rebuilt.
+fn write_repository(config: &RuntimeConfig, dir: &Path) -> Result<()> {
+    let plugins: Vec<Plugin> = get_managed_plugins(config);
+
+    let mut packages = Vec::new();
+    let mut plugin_table = Table::new();
+
+    plugin_table.add_row(row!["Plugin", "Package name", "Versions", "Notes"]);
+
+    for plugin in plugins {
+        let p_nicename = plugin.get_nicename();
+
+        if let Err(e) = get_repository_git_dir(config, &plugin) {
+            plugin_table.add_row(row![&p_nicename, &plugin.package_name, "0", format!("{}", e)]);
+            continue;
+        }
+
+        let mut package = get_package_versions(config, &plugin)?;
+
+        if let (Some(artifacts_dir), false) = (config.get_artifacts_dir(), config.dry_run) {
+            add_dist_entries(config, &plugin, &mut package, &artifacts_dir, dir)?;
+        }
+
+        let notes = match package.skipped_tags.is_empty() {
+            true => String::new(),
+            false => format!("Skipped invalid version tags: {}", package.skipped_tags.join(", "))
+        };
+
+        plugin_table.add_row(row![&p_nicename, &plugin.package_name, package.versions.len(), &notes]);
+
+        packages.push(package);
+    }
+
+    plugin_table.printstd();
+
+    let packages_json = serde_json::to_string_pretty(&build_packages_json(&packages))?;
+    let packages_file = dir.join(PACKAGES_FILE_NAME);
+
+    if config.dry_run {
+        println!("Would write `{}`:\n{}", packages_file.display(), packages_json);
+
+        return Ok(());
+    }
+
+    let temp_file = dir.join(format!(".{}.tmp", PACKAGES_FILE_NAME));
+
+    create_dir_all(dir)?;
+    write(&temp_file, packages_json)?;
+    rename(&temp_file, &packages_file)?;
+
+    println!("Repository written to `{}`", packages_file.display());
+
+    Ok(())
+}
+
+/// Serves the Composer repository of managed plugins over HTTP. The
+/// repository is rebuilt before the server starts.
+pub fn serve(config: RuntimeConfig, listen: &str) -> Result<bool> {
+    let export_dir = config.get_export_dir();
+
+    write_repository(&config, &export_dir)?;
+
+    if config.repository.url.is_none() && config.get_artifacts_dir().is_some() {
+        eprintln!("Warning: `repository.url` is not configured, dist archive URLs will not be reachable");
+    }
+
+    let repository_server = RepositoryServer::new(export_dir, config.repository.auth.as_ref());
+    let server = RepositoryServer::bind(listen)?;
+
+    println!("Serving Composer repository on http://{}", listen);
+
+    repository_server.serve(&server);
+
+    Ok(true)
+}
+
+/// Get the tag of the version a plugin is rolled back to: the tag of the
+/// given version, or the newest tag older than the installed version.
+fn get_rollback_tag(git: &dyn VcsBackend, plugin: &Plugin, to: Option<&str>) -> Result<(Version, String)> {
+    let installed = Version::parse(&plugin.installed_version.clone().unwrap_or_default())?;
+
+    let mut tags: Vec<(Version, String)> = git
+        .list_tags()?
+        .into_iter()
+        .filter_map(|t| Version::parse(&t).ok().map(|v| (v, t)))
+        .collect();
+
+    tags.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let tag = match to {
+        Some(v) => {
+            let target = Version::parse(v)?;
+
+            tags.into_iter().find(|t| t.0 == target)
+        }
+        None => tags.into_iter().rev().find(|t| t.0 < installed)
+    };
+
+    match (tag, to) {
+        (Some(t), _) => Ok(t),
+        (None, Some(v)) => Err(Error::Version(format!(
+            "Plugin `{}` has no tagged version {}",
+            plugin.get_nicename(),
+            v
+        ))),
+        (None, None) => Err(Error::Version(format!(
+            "Plugin `{}` has no tagged version older than {}",
+            plugin.get_nicename(),
+            installed
+        )))
+    }
+}
+
+/// Rolls a managed plugin back to a previously tagged version, and optionally
+/// holds it at that version so that `run` does not upgrade it again.
+pub fn rollback(config: RuntimeConfig, slug: &str, to: Option<&str>, hold: bool) -> Result<bool> {
+    let plugin_config = match config.find_plugin_config(slug) {
+        Some(p) => p.clone(),
+        None => return Err(Error::Plugin(format!("Plugin `{}` is not being managed", slug)))
+    };
+
+    let plugin = Plugin::from_config(plugin_config.clone(), &config.get_index_path_base(&plugin_config));
+    let plugin_dir = plugin.get_plugin_dir();
+
+    plugin.validate()?;
+
+    let mirror_dir = config.get_mirror_dir(&plugin)?;
+    let repository_dir = config.get_repository_dir(&plugin)?;
+    let git = get_backend(config.binaries.git.clone(), config.git.clone(), repository_dir.clone());
+
+    if !repository_dir.join(".git").is_dir() || !git.repository_is_initialized()? {
+        return Err(Error::Plugin(format!(
+            "Plugin `{}` has no git repository to roll back with",
+            plugin.get_nicename()
+        )));
+    }
+
+    let (version, tag) = get_rollback_tag(&*git, &plugin, to)?;
+    let installed = plugin.installed_version.clone().unwrap_or_default();
+
+    if config.dry_run {
+        println!("Would roll back `{}` from {} to {} (tag `{}`)", plugin.get_nicename(), installed, version, tag);
+    } else {
+        let backups = BackupStore::new(maybe_create_backups_directory(&config)?);
+        let backup = backups.create(&plugin, git.get_commit_hash("HEAD").ok())?;
+
+        backups.prune(&backup.plugin, config.backups.get_keep())?;
+
+        git.checkout_tree(&tag)?;
+
+        let index_path = match plugin.index_path.file_name() {
+            Some(index_file) if mirror_dir.is_some() => repository_dir.join(index_file),
+            _ => plugin.index_path.clone()
+        };
+
+        let header_version = PluginHeader::from_file(&index_path)
+            .ok()
+            .and_then(|h| h.version)
+            .and_then(|v| Version::parse(&v).ok());
+
+        if header_version.as_ref() != Some(&version) {
+            match mirror_dir {
+                Some(_) => {
+                    git.reset_contents()?;
+                },
+                None => restore_plugin_backup(&*git, &backups, &backup, &plugin_dir)?
+            };
+
+            return Err(Error::Version(format!(
+                "Plugin header of `{}` at tag `{}` does not have version {}, rollback was undone",
+                plugin.get_nicename(),
+                tag,
+                version
+            )));
+        }
+
+        if git.has_staged_changes()? {
+            git.commit_staged(&format!("Roll back {} to {}", slug, version))?;
+        }
+
+        if mirror_dir.is_some() {
+            sync_directory(&repository_dir, &plugin_dir, &MIRROR_FILES)?;
+        }
+
+        println!("Rolled back `{}` from {} to {}", plugin.get_nicename(), installed, version);
+    }
+
+    if hold {
+        let mut editor = ConfigEditor::open(&get_config_file(&config)?)?;
+
+        editor.set_plugin_value(&plugin_config.index_path, "hold", &version.to_string())?;
+        save_config_editor(&config, &editor)?;
+
+        println!("Holding `{}` at {}, remove `hold` from its configuration to resume upgrades", plugin.get_nicename(), version);
+    }
+
+    Ok(true)
+}
+
+/// Get the managed plugins which have an update available in WordPress.
+pub fn get_outdated_plugins(config: &RuntimeConfig) -> Result<Vec<OutdatedPlugin>> {
+    let base_dir = config.wp_root.clone().unwrap_or_else(|| config.cwd.clone());
+    let updates = WpCli::new(config.binaries.wpcli.clone(), base_dir).list_plugin_updates()?;
+
+    let mut outdated = Vec::new();
+
+    for plugin in get_managed_plugins(config) {
+        let slug = match plugin.get_cli_name() {
+            Ok(s) => s,
+            Err(_) => continue
+        };
+
+        let update = match updates.iter().find(|u| u.name == slug) {
+            Some(u) => u,
+            None => continue
+        };
+
+        let available = update.update_version.clone().filter(|v| !v.is_empty());
+        let current = plugin.installed_version.clone().unwrap_or_else(|| update.version.clone());
+
+        let blocked_reason = match (Version::parse(&current), available.as_ref().map(|v| Version::parse(v))) {
+            (Ok(ref c), Some(Ok(ref a))) => plugin.policy.check(c, a),
+            _ => Ok(None)
+        };
+
+        let action = match (&plugin.hold, blocked_reason) {
+            (&Some(_), _) => "held".to_string(),
+            (&None, Err(e)) => format!("error: {}", e),
+            (&None, Ok(Some(reason))) => format!("blocked by policy: {}", reason),
+            (&None, Ok(None)) => "upgrade".to_string()
+        };
+
+        outdated.push(OutdatedPlugin {
+            plugin: slug,
+            package_name: plugin.package_name.clone(),
+            current,
+            available,
+            action,
+        });
+    }
+
+    Ok(outdated)
+}
+
+/// Lists managed plugins which have updates available, as a table or as
+/// JSON. Returns the number of plugins `run` would upgrade.
+pub fn outdated(config: RuntimeConfig, json: bool) -> Result<usize> {
+    let outdated = get_outdated_plugins(&config)?;
+    let upgradable = outdated.iter().filter(|p| p.is_upgradable()).count();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&outdated)?);
+
+        return Ok(upgradable);
+    }
+
+    if outdated.is_empty() {
+        println!("All managed plugins are up to date");
+
+        return Ok(0);
+    }
+
+    let mut plugin_table = Table::new();
+
+    plugin_table.add_row(row!["Plugin", "Package name", "Current", "Available", "Action"]);
+
+    for plugin in &outdated {
+        plugin_table.add_row(row![
+            &plugin.plugin,
+            &plugin.package_name,
+            &plugin.current,
+            &plugin.available.clone().unwrap_or("unknown".to_string()),
+            &plugin.action
+        ]);
+    }
+
+    plugin_table.printstd();
+
+    Ok(upgradable)
+}
+
+/// Verify the signatures of all tags in the repositories of managed plugins.
+/// Plugins without a repository are reported with an empty tag.
+pub fn get_tag_verifications(config: &RuntimeConfig) -> Result<Vec<TagVerification>> {
+    let allowed_signers = match config.get_allowed_signers_file() {
+        Some(f) => f,
+        None => {
+            return Err(Error::Config(ConfigError::new(
+                "An allowed signers file must be configured in `git.allowed_signers` to verify tags"
+            )))
+        }
+    };
+
+    let mut verifications = Vec::new();
+
+    for plugin in get_managed_plugins(config) {
+        let p_nicename = plugin.get_nicename();
+
+        if let Err(e) = get_repository_git_dir(config, &plugin) {
+            verifications.push(TagVerification {
+                plugin: p_nicename,
+                tag: String::new(),
+                error: Some(format!("{}", e)),
+            });
+
+            continue;
+        }
+
+        let git = get_backend(config.binaries.git.clone(), config.git.clone(), config.get_repository_dir(&plugin)?);
+
+        for tag in git.list_tags()? {
+            let error = git.verify_tag(&tag, &allowed_signers)?;
+
+            verifications.push(TagVerification {
+                plugin: p_nicename.clone(),
+                tag,
+                error,
+            });
+        }
+    }
+
+    Ok(verifications)
+}
+
+/// Verifies that every tag of the managed plugin repositories is signed by a
+/// trusted key. Fails if any tag is not.
+pub fn verify(config: RuntimeConfig) -> Result<bool> {
+    let verifications = get_tag_verifications(&config)?;
+
+    let mut tag_table = Table::new();
+
+    tag_table.add_row(row!["Plugin", "Tag", "Signature"]);
+
+    for verification in &verifications {
+        tag_table.add_row(row![
+            &verification.plugin,
+            &verification.tag,
+            verification.error.as_deref().unwrap_or("trusted")
+        ]);
+    }
+
+    tag_table.printstd();
+
+    let untrusted = verifications.iter().filter(|v| v.error.is_some()).count();
+
+    if untrusted > 0 {
+        return Err(Error::Plugin(format!(
+            "{} of {} tags are not signed by a trusted key",
+            untrusted,
+            verifications.len()
+        )));
+    }
+
+    Ok(true)
+}
+
+/// Format a byte count for display.
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1024 * 1024 => format!("{:.1} MiB", b as f64 / (1024.0 * 1024.0)),
+        b if b >= 1024 => format!("{:.1} KiB", b as f64 / 1024.0),
+        b => format!("{} B", b)
+    }
+}
+
+/// Lists plugin backups, optionally only those of a single plugin.
+pub fn backup_list(config: RuntimeConfig, slug: Option<&str>) -> Result<bool> {
+    let backups = BackupStore::new(config.get_backups_dir());
+
+    let entries = match slug {
+        Some(s) => backups.get_plugin_backups(s)?,
+        None => backups.load_index()?
+    };
+
+    if entries.is_empty() {
+        println!("No backups found");
+        return Ok(true);
+    }
+
+    let mut backup_table = Table::new();
+
+    backup_table.add_row(row!["Backup", "Plugin", "Version", "Commit", "Created", "Files", "Size"]);
+
+    for entry in entries {
+        let commit: String = entry.commit.clone().unwrap_or_default().chars().take(8).collect();
+
+        backup_table.add_row(row![
+            &entry.id,
+            &entry.plugin,
+            &entry.version.clone().unwrap_or("unknown".to_string()),
+            &commit,
+            &entry.created,
+            entry.files,
+            format_size(entry.size)
+        ]);
+    }
+
+    backup_table.printstd();
+
+    Ok(true)
+}
+
+/// Restores a managed plugin directory from one of its backups.
+pub fn backup_restore(config: RuntimeConfig, slug: &str, backup_id: &str) -> Result<bool> {
+    let plugin_config = match config.find_plugin_config(slug) {
+        Some(p) => p.clone(),
+        None => return Err(Error::Plugin(format!("Plugin `{}` is not being managed", slug)))
+    };
+
+    let backups = BackupStore::new(config.get_backups_dir());
+
+    let backup = match backups.get_plugin_backups(slug)?.into_iter().find(|e| e.id == backup_id) {
+        Some(b) => b,
+        None => {
+            return Err(Error::Backup(format!(
+                "Backup `{}` of plugin `{}` does not exist",
+                backup_id,
+                slug
+            )));
+        }
+    };
+
+    let plugin = Plugin::from_config(plugin_config.clone(), &config.get_index_path_base(&plugin_config));
+    let plugin_dir = plugin.get_plugin_dir();
+
+    if config.dry_run {
+        backups.verify(&backup)?;
+
+        println!("Would restore `{}` from backup `{}`", plugin_dir.display(), backup.id);
+
+        return Ok(true);
+    }
+
+    // backups of mirrored plugins do not contain git history
+    match config.get_mirror_dir(&plugin)? {
+        Some(_) => backups.restore(&backup, &plugin_dir)?,
+        None => {
+            let git = get_backend(config.binaries.git.clone(), config.git.clone(), plugin_dir.clone());
+
+            restore_plugin_backup(&*git, &backups, &backup, &plugin_dir)?;
+        }
+    };
+
+    println!(
+        "Restored plugin `{}` to version {} from backup `{}`",
+        plugin.get_nicename(),
+        backup.version.unwrap_or("unknown".to_string()),
+        backup.id
+    );
+
+    Ok(true)
+}
+
+/// Removes old backups of all plugins which exceed the configured, or the
+/// given, number of backups to keep.
+pub fn backup_prune(config: RuntimeConfig, keep: Option<usize>) -> Result<bool> {
+    let backups = BackupStore::new(config.get_backups_dir());
+    let keep = keep.unwrap_or_else(|| config.backups.get_keep());
+
+    let mut slugs: Vec<String> = backups.load_index()?.into_iter().map(|e| e.plugin).collect();
+    slugs.sort();
+    slugs.dedup();
+
+    let mut removed_count = 0;
+
+    for slug in slugs {
+        let removed = match config.dry_run {
+            true => backups.get_prunable(&slug, keep)?,
+            false => backups.prune(&slug, keep)?
+        };
+
+        for entry in removed {
+            match config.dry_run {
+                true => println!("Would remove backup `{}`", entry.id),
+                false => println!("Removed backup `{}`", entry.id)
+            };
+
+            removed_count += 1;
+        }
+    }
+
+    if removed_count == 0 {
+        println!("No backups to prune");
+    }
+
+    Ok(true)
 }
 
 /// Lists managed WordPress plugins.
-pub fn list(config: RuntimeConfig) -> Result<bool, String> {
+pub fn list(config: RuntimeConfig) -> Result<bool> {
     println!("Listing managed plugins");
 
     let plugins: Vec<Plugin> = get_managed_plugins(&config);
@@ -61,12 +1045,12 @@ pub fn list(config: RuntimeConfig) -> Result<bool, String> {
 
     let mut plugin_table = Table::new();
 
-    plugin_table.add_row(row!["Plugin", "Valid", "Version", "Package name", "Remote"]);
+    plugin_table.add_row(row!["Plugin", "Valid", "Version", "Package name", "Remote", "Notes"]);
 
     for plugin in plugins {
-        let validity = match plugin.is_valid() {
-            true => "true",
-            false => "false",
+        let (validity, notes) = match plugin.validate() {
+            Ok(_) => ("true", String::new()),
+            Err(e) => ("false", format!("{}", e)),
         };
 
         plugin_table.add_row(row![
@@ -74,7 +1058,8 @@ pub fn list(config: RuntimeConfig) -> Result<bool, String> {
             &validity,
             &plugin.installed_version.unwrap_or("unknown".to_string()),
             &plugin.package_name,
-            &plugin.remote_repository
+            &plugin.remote_repository,
+            &notes
         ]);
     }
 
@@ -84,7 +1069,7 @@ pub fn list(config: RuntimeConfig) -> Result<bool, String> {
 }
 
 /// Runs upgrades and gitifications on managed WordPress plugins.
-pub fn run(config: RuntimeConfig) -> Result<bool, String> {
+pub fn run(config: RuntimeConfig) -> Result<bool> {
     let plugins: Vec<Plugin> = get_managed_plugins(&config);
 
     if plugins.len() < 1 {
@@ -95,39 +1080,98 @@ pub fn run(config: RuntimeConfig) -> Result<bool, String> {
     // create a directory for plugin backups
     let backup_dir = maybe_create_backups_directory(&config)?;
 
+    run_hooks(&config, "pre_upgrade", &config.pre_upgrade, &[])?;
+
+    let mut summary = RunSummary::default();
     let mut plugin_table = Table::new();
 
     plugin_table.add_row(row!["Plugin", "Result", "Notes"]);
 
     for plugin in plugins {
-        let valid = plugin.is_valid();
         let p_nicename = plugin.get_nicename();
 
-        if !valid {
-            plugin_table.add_row(row![
-                &p_nicename,
-                "false",
-                "Plugin invalid, cannot run upgrades"
-            ]);
+        if let Err(e) = plugin.validate() {
+            let error = format!("Plugin invalid, cannot run upgrades: {}", e);
+
+            plugin_table.add_row(row![&p_nicename, "false", &error]);
+
+            summary.failed.push(FailedPlugin {
+                plugin: p_nicename,
+                package_name: plugin.package_name.clone(),
+                error,
+            });
 
             continue;
         }
 
-        let mut pipeline = Pipeline::new(&config, &plugin, &backup_dir)?;
+        if let Some(ref hold) = plugin.hold {
+            let notes = match *hold {
+                PluginHold::Version(ref v) => format!("Held at {}", v),
+                PluginHold::Enabled(_) => "Held".to_string(),
+            };
+
+            plugin_table.add_row(row![&p_nicename, "held", &notes]);
+
+            summary.skipped.push(p_nicename);
 
-        let result = pipeline.run();
+            continue;
+        }
+
+        let result = Pipeline::new(&config, &plugin, &backup_dir).and_then(|mut p| p.run());
 
         match result {
+            Ok(UpgradeOutcome::Upgraded { from, to }) => {
+                plugin_table.add_row(row![&p_nicename, "ok", format!("{} -> {}", from, to)]);
+
+                summary.upgraded.push(UpgradedPlugin {
+                    plugin: p_nicename,
+                    package_name: plugin.package_name.clone(),
+                    from,
+                    to,
+                });
+            }
+            Ok(UpgradeOutcome::BlockedByPolicy { available, reason }) => {
+                let notes = format!("Update to {} available, blocked by policy: {}", available, reason);
+
+                plugin_table.add_row(row![&p_nicename, "blocked", &notes]);
+
+                summary.skipped.push(p_nicename);
+            }
             Ok(_) => {
                 plugin_table.add_row(row![&p_nicename, "ok", ""]);
+
+                summary.skipped.push(p_nicename);
             }
             Err(e) => {
-                plugin_table.add_row(row![&p_nicename, "error", e]);
+                let error = format!("{}", e);
+
+                plugin_table.add_row(row![&p_nicename, "error", &error]);
+
+                summary.failed.push(FailedPlugin {
+                    plugin: p_nicename,
+                    package_name: plugin.package_name.clone(),
+                    error,
+                });
             }
         };
     }
 
     plugin_table.printstd();
 
+    let export_dir = config.get_export_dir();
+    let mut repository_result = Ok(());
+
+    if !summary.upgraded.is_empty() && (config.repository.export_dir.is_some() || export_dir.exists()) {
+        println!("Rebuilding Composer repository");
+
+        repository_result = write_repository(&config, &export_dir);
+    }
+
+    // post-upgrade hooks run even if the repository could not be rebuilt
+    let hooks_result = run_post_upgrade_hooks(&config, &summary);
+
+    repository_result?;
+    hooks_result?;
+
     Ok(true)
 }
@@ -0,0 +1,222 @@
+//! # composer
+//!
+//! Generation of composer.json files for managed plugins.
+
+use serde::Serialize;
+use serde_json::{self, ser::PrettyFormatter, Map, Value};
+
+use config::ComposerStrategy;
+use error::{Error, Result};
+use wordpress::{Plugin, PluginHeader};
+
+/// Version constraint used for the `composer/installers` requirement, which
+/// installs `wordpress-plugin` packages into the plugins directory.
+const COMPOSER_INSTALLERS_CONSTRAINT: &str = "^1.0 || ^2.0";
+
+/// Key under `extra` which holds plugin metadata and marks a composer.json as
+/// generated by this tool.
+pub const EXTRA_KEY: &str = "wppr";
+
+/// Generate composer.json contents for a plugin from its header data.
+pub fn generate_composerjson(plugin: &Plugin, header: &PluginHeader) -> Value {
+    let mut composerjson = Map::new();
+
+    composerjson.insert("name".to_string(), Value::from(plugin.package_name.clone()));
+
+    if let Some(ref description) = header.description {
+        composerjson.insert("description".to_string(), Value::from(description.clone()));
+    }
+
+    composerjson.insert("type".to_string(), Value::from("wordpress-plugin"));
+
+    if let Some(ref license) = header.license {
+        composerjson.insert("license".to_string(), Value::from(get_spdx_license(license)));
+    }
+
+    if let Some(ref homepage) = header.plugin_uri {
+        composerjson.insert("homepage".to_string(), Value::from(homepage.clone()));
+    }
+
+    if let Some(ref author) = header.author {
+        let mut author_data = Map::new();
+
+        author_data.insert("name".to_string(), Value::from(author.clone()));
+
+        if let Some(ref author_uri) = header.author_uri {
+            author_data.insert("homepage".to_string(), Value::from(author_uri.clone()));
+        }
+
+        composerjson.insert("authors".to_string(), Value::Array(vec![Value::Object(author_data)]));
+    }
+
+    let mut require = Map::new();
+
+    if let Some(ref php) = header.requires_php {
+        require.insert("php".to_string(), Value::from(get_php_constraint(php)));
+    }
+
+    require.insert("composer/installers".to_string(), Value::from(COMPOSER_INSTALLERS_CONSTRAINT));
+
+    composerjson.insert("require".to_string(), Value::Object(require));
+
+    let mut extra = Map::new();
+
+    if let Ok(slug) = plugin.get_cli_name() {
+        extra.insert("installer-name".to_string(), Value::from(slug));
+    }
+
+    extra.insert(EXTRA_KEY.to_string(), get_plugin_metadata(plugin, header));
+
+    composerjson.insert("extra".to_string(), Value::Object(extra));
+
+    Value::Object(composerjson)
+}
+
+/// Get composer.json contents for a plugin, taking an existing composer.json
+/// and the composer configuration of the plugin into account. Returns `None`
+/// if the existing composer.json should be kept as is.
+///
+/// A composer.json generated earlier is always regenerated, so header changes
+/// and the composer overrides of the plugin are applied regardless of the
+/// composer strategy. A composer.json shipped by the plugin vendor is handled
+/// according to the composer strategy of the plugin.
+pub fn get_plugin_composerjson(plugin: &Plugin, header: &PluginHeader, existing: Option<&str>) -> Result<Option<Value>> {
+    let base: Option<Value> = match existing {
+        Some(c) if is_generated_composerjson(c, &plugin.package_name) => None,
+        Some(c) => match plugin.composer_strategy {
+            ComposerStrategy::Keep => return Ok(None),
+            ComposerStrategy::Overwrite => None,
+            ComposerStrategy::Merge => match serde_json::from_str(c) {
+                Ok(v) => Some(v),
+                Err(e) => {
+                    return Err(Error::Plugin(format!(
+                        "Cannot merge composer.json shipped with plugin `{}`: {}",
+                        plugin.get_nicename(),
+                        e
+                    )));
+                }
+            }
+        },
+        None => None
+    };
+
+    let mut composerjson = base.unwrap_or_else(|| Value::Object(Map::new()));
+
+    merge_values(&mut composerjson, generate_composerjson(plugin, header));
+
+    if let Some(ref overrides) = plugin.composer_overrides {
+        merge_values(&mut composerjson, overrides.clone());
+    }
+
+    Ok(Some(composerjson))
+}
+
+/// Deep-merge a value over another. Objects are merged key by key, all other
+/// values are replaced.
+pub fn merge_values(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (&mut Value::Object(ref mut base_map), Value::Object(overlay_map)) => {
+            for (k, v) in overlay_map {
+                match base_map.get_mut(&k) {
+                    Some(existing) => merge_values(existing, v),
+                    None => {
+                        base_map.insert(k, v);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Get plugin metadata which has no standard place in composer.json.
+fn get_plugin_metadata(plugin: &Plugin, header: &PluginHeader) -> Value {
+    let mut metadata = Map::new();
+
+    metadata.insert("plugin-file".to_string(), Value::from(plugin.get_nicename()));
+
+    let optional_values = vec![
+        ("plugin-name", &header.name),
+        ("version", &header.version),
+        ("requires-wp", &header.requires_wp),
+        ("text-domain", &header.text_domain),
+        ("update-uri", &header.update_uri),
+    ];
+
+    for (key, value) in optional_values {
+        if let Some(ref v) = *value {
+            metadata.insert(key.to_string(), Value::from(v.clone()));
+        }
+    }
+
+    if !header.requires_plugins.is_empty() {
+        metadata.insert("requires-plugins".to_string(), Value::from(header.requires_plugins.clone()));
+    }
+
+    if header.network {
+        metadata.insert("network".to_string(), Value::from(true));
+    }
+
+    Value::Object(metadata)
+}
+
+/// Get a Composer version constraint from a `Requires PHP` header value,
+/// e.g. `7.2` becomes `>=7.2`.
+fn get_php_constraint(requires_php: &str) -> String {
+    match requires_php.starts_with(|c: char| c.is_ascii_digit()) {
+        true => format!(">={}", requires_php),
+        false => requires_php.to_string()
+    }
+}
+
+/// Get an SPDX license identifier for common WordPress plugin license
+/// declarations. Unknown licenses are returned as is.
+fn get_spdx_license(license: &str) -> String {
+    let compact: String = license
+        .to_lowercase()
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect();
+
+    let spdx = match compact.as_str() {
+        "gpl2+" | "gplv2+" | "gpl2.0+" | "gplv2orlater" | "gpl2orlater" | "gpl2.0orlater" => "GPL-2.0-or-later",
+        "gpl2" | "gplv2" | "gpl2.0" | "gpl2.0only" => "GPL-2.0-only",
+        "gpl3+" | "gplv3+" | "gpl3.0+" | "gplv3orlater" | "gpl3orlater" | "gpl3.0orlater" => "GPL-3.0-or-later",
+        "gpl3" | "gplv3" | "gpl3.0" | "gpl3.0only" => "GPL-3.0-only",
+        "mit" => "MIT",
+        _ => return license.to_string()
+    };
+
+    spdx.to_string()
+}
+
+/// Format composer.json contents the way Composer does, with four space
+/// indentation.
+pub fn to_json_string(composerjson: &Value) -> String {
+    let mut data: Vec<u8> = Vec::new();
+    let formatter = PrettyFormatter::with_indent(b"    ");
+    let mut serializer = serde_json::Serializer::with_formatter(&mut data, formatter);
+
+    // serializing a `Value` into memory cannot fail
+    composerjson.serialize(&mut serializer).unwrap();
+
+    format!("{}\n", String::from_utf8_lossy(&data))
+}
+
+/// Get the composer.json contents earlier versions of this tool generated.
+fn get_legacy_composerjson(package_name: &str) -> String {
+    format!("{{\"name\": \"{}\"\"type\": \"wordpress-plugin\"}}", package_name)
+}
+
+/// Is a composer.json generated by this tool? Files shipped by plugin vendors
+/// lack the `extra.wppr` metadata.
+pub fn is_generated_composerjson(contents: &str, package_name: &str) -> bool {
+    if contents == get_legacy_composerjson(package_name) {
+        return true;
+    }
+
+    match serde_json::from_str::<Value>(contents) {
+        Ok(v) => v.get("extra").and_then(|e| e.get(EXTRA_KEY)).is_some(),
+        Err(_) => false
+    }
+}
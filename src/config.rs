@@ -2,11 +2,31 @@
 //!
 //! Configuration related functionalities.
 
+use regex::Regex;
+use serde_ignored;
 use toml;
-use std::path::{PathBuf};
+use std::path::{Path, PathBuf};
 use std::io::Read;
-use std::fs::File;
+use std::fs::{read_dir, File};
 use std::process::{Command, Stdio};
+use std::fmt;
+use std::result;
+use std::sync::OnceLock;
+
+use error::{ConfigError, Error, Result};
+use version::VersionBump;
+use wordpress::Plugin;
+
+/// Commit message template of upgrade commits when none has been configured.
+pub const DEFAULT_COMMIT_MESSAGE: &str = "Update {plugin} from {old_version} to {new_version}";
+
+/// Name of the configuration file that is searched for when no configuration
+/// file is given explicitly.
+pub const CONFIG_FILE_NAME: &str = "wppr.toml";
+
+/// How many directory levels below the configuration directory are searched
+/// for a WordPress installation.
+const WP_ROOT_SEARCH_DEPTH: usize = 3;
 
 //LCOV_EXCL_START
 /// Struct to hold deserialized TOML configuration.
@@ -15,9 +35,17 @@ pub struct TomlConfig {
     pub binaries: Option<BinariesConfig>,
     pub git: Option<GitConfig>,
     pub plugins: Option<Vec<PluginConfig>>,
+    pub pre_upgrade: Option<HooksConfig>,
+    pub post_upgrade: Option<HooksConfig>,
+    pub repository: Option<RepositoryConfig>,
+    pub backups: Option<BackupsConfig>,
     pub verbose: Option<bool>,
     pub dry_run: Option<bool>,
     pub cwd: Option<String>,
+    #[serde(skip)]
+    pub file: Option<PathBuf>,
+    #[serde(skip)]
+    pub unknown_keys: Vec<String>,
 }
 
 /// Configuration of binaries used when running the tool.
@@ -25,23 +53,98 @@ pub struct TomlConfig {
 pub struct BinariesConfig {
     pub git: String,
     pub wpcli: String,
+    pub shell: Option<String>,
 }
 
 impl Default for BinariesConfig {
     fn default() -> Self {
         BinariesConfig {
             git: "git".to_string(),
-            wpcli: "wp".to_string()
+            wpcli: "wp".to_string(),
+            shell: None
         }
     }
 }
 
+impl BinariesConfig {
+    /// Get the shell used for running user defined commands, defaults to `sh`.
+    pub fn get_shell(&self) -> String {
+        self.shell.clone().unwrap_or_else(|| "sh".to_string())
+    }
+}
+
 /// Configuration for Git when running the tool.
 #[derive(Debug, Deserialize, Clone)]
 pub struct GitConfig {
     pub user_name: String,
     pub user_email: String,
     pub force_push: bool,
+    pub backend: Option<GitBackend>,
+    pub mirrors_dir: Option<String>,
+    pub commit_message: Option<String>,
+    pub sign: Option<SigningFormat>,
+    pub signing_key: Option<String>,
+    pub allowed_signers: Option<String>,
+}
+
+/// Implementation used for git operations.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum GitBackend {
+    /// The git binary configured in `[binaries]`.
+    #[default]
+    Cli,
+    /// libgit2, linked into wppr.
+    Libgit2,
+}
+
+/// Signature format of signed commits and tags.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SigningFormat {
+    /// OpenPGP signatures made with gpg.
+    Gpg,
+    /// SSH signatures made with ssh-keygen.
+    Ssh,
+}
+
+/// Shell commands to run before or after the `run` command.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct HooksConfig {
+    pub commands: Vec<String>,
+    pub timeout: Option<u64>,
+}
+
+/// Configuration of the exported Composer repository and release artifacts.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct RepositoryConfig {
+    pub url: Option<String>,
+    pub artifacts_dir: Option<String>,
+    pub export_dir: Option<String>,
+    pub auth: Option<BasicAuthConfig>,
+}
+
+/// Configuration of plugin backups made before upgrades.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct BackupsConfig {
+    pub keep: Option<usize>,
+}
+
+/// HTTP basic auth credentials required from Composer repository clients.
+#[derive(Deserialize, Clone)]
+pub struct BasicAuthConfig {
+    pub username: String,
+    pub password: String,
+}
+
+/// The password is redacted, configs are printed with `--verbose`.
+impl fmt::Debug for BasicAuthConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BasicAuthConfig")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
 }
 
 /// Plugins configuration when running the tool.
@@ -51,9 +154,113 @@ pub struct PluginConfig {
     pub package_name: String,
     pub remote_repository: String,
     pub pre_cmds: Option<Vec<String>>,
+    pub post_cmds: Option<Vec<String>>,
+    pub cmds_timeout: Option<u64>,
+    pub composer: Option<toml::value::Table>,
+    pub composer_strategy: Option<ComposerStrategy>,
+    pub hold: Option<PluginHold>,
+    pub allow: Option<VersionBump>,
+    pub max_version: Option<String>,
+}
+
+/// How a composer.json shipped by a plugin vendor is treated.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ComposerStrategy {
+    /// Generated composer.json is merged over the vendor composer.json.
+    Merge,
+    /// Vendor composer.json is replaced with a generated one.
+    Overwrite,
+    /// Vendor composer.json is kept as is.
+    #[default]
+    Keep,
+}
+
+/// Hold of a plugin, which stops upgrades. Either `true`, or the version the
+/// plugin is held at.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum PluginHold {
+    Enabled(bool),
+    Version(String),
 }
 //LCOV_EXCL_STOP
 
+impl PluginHold {
+    /// Is the plugin held? `hold = false` does not hold a plugin.
+    pub fn is_held(&self) -> bool {
+        match *self {
+            PluginHold::Enabled(held) => held,
+            PluginHold::Version(_) => true,
+        }
+    }
+}
+
+impl ComposerStrategy {
+    /// Get the strategy as it is written in configuration files.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            ComposerStrategy::Merge => "merge",
+            ComposerStrategy::Overwrite => "overwrite",
+            ComposerStrategy::Keep => "keep",
+        }
+    }
+}
+
+impl GitConfig {
+    /// Get the commit message template of upgrade commits.
+    pub fn get_commit_message(&self) -> String {
+        self.commit_message.clone().unwrap_or_else(|| DEFAULT_COMMIT_MESSAGE.to_string())
+    }
+}
+
+impl GitBackend {
+    /// Get the backend as it is written in configuration files.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            GitBackend::Cli => "cli",
+            GitBackend::Libgit2 => "libgit2",
+        }
+    }
+}
+
+impl SigningFormat {
+    /// Get the format as it is written in configuration files.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            SigningFormat::Gpg => "gpg",
+            SigningFormat::Ssh => "ssh",
+        }
+    }
+
+    /// Get the format as it is given to git in `gpg.format`.
+    pub fn get_gpg_format(&self) -> &'static str {
+        match *self {
+            SigningFormat::Gpg => "openpgp",
+            SigningFormat::Ssh => "ssh",
+        }
+    }
+}
+
+impl BackupsConfig {
+    /// Get how many backups are kept per plugin, defaults to 5. At least one
+    /// backup is always kept.
+    pub fn get_keep(&self) -> usize {
+        self.keep.unwrap_or(5).max(1)
+    }
+}
+
+impl PluginConfig {
+    /// Get the plugin slug, which is the name of the directory the plugin
+    /// index file resides in.
+    pub fn get_slug(&self) -> Option<String> {
+        Path::new(&self.index_path)
+            .parent()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().to_string())
+    }
+}
+
 impl TomlConfig {
     pub fn set_verbosity(&mut self, verbosity: bool) {
         self.verbose = Some(verbosity);
@@ -67,51 +274,261 @@ impl TomlConfig {
         self.cwd = Some(cwd);
     }
 
-    pub fn load_from_file(file: PathBuf) -> Result<TomlConfig, &'static str> {
+    pub fn load_from_file(file: PathBuf) -> Result<TomlConfig> {
         if !file.is_absolute() {
-            return Err("Configuration file must be given as an absolute path");
+            return Err(Error::Config(
+                ConfigError::new("Configuration file must be given as an absolute path").with_file(&file)
+            ));
         }
 
         if !file.exists() {
-            return Err("Invalid configuration, please validate the configuration file exists");
+            return Err(Error::Config(
+                ConfigError::new("Please validate the configuration file exists").with_file(&file)
+            ));
         }
 
-        let config_cwd: &str = match file.parent().unwrap().to_str() {
+        let config_cwd: &str = match file.parent().and_then(|p| p.to_str()) {
             Some(s) => s,
             None => {
-                return Err(
-                    "Invalid configuration location given, please validate your config is in an \
-                     accessable location",
-                );
+                return Err(Error::Config(
+                    ConfigError::new(
+                        "Invalid configuration location given, please validate your config is in \
+                         an accessable location"
+                    ).with_file(&file)
+                ));
             }
         };
 
         let mut cfg_data: String = String::new();
 
-        File::open(file.to_str().unwrap())
-            .unwrap()
-            .read_to_string(&mut cfg_data)
-            .unwrap();
+        if let Err(e) = File::open(&file).and_then(|mut f| f.read_to_string(&mut cfg_data)) {
+            return Err(Error::Config(
+                ConfigError::new(&format!("Could not read configuration file: {}", e)).with_file(&file)
+            ));
+        }
 
-        let mut toml_configuration: TomlConfig = toml::from_str(&cfg_data).unwrap();
+        let mut toml_configuration: TomlConfig = match TomlConfig::from_toml_str(&cfg_data) {
+            Ok(c) => c,
+            Err(e) => return Err(Error::Config(e.with_file(&file)))
+        };
 
         toml_configuration.set_cwd(config_cwd.to_string());
+        toml_configuration.file = Some(file);
+
+        Ok(toml_configuration)
+    }
+
+    /// Parse configuration from a TOML string, collecting unknown keys which
+    /// were ignored during parsing.
+    pub fn from_toml_str(data: &str) -> result::Result<TomlConfig, ConfigError> {
+        let mut unknown_keys: Vec<String> = Vec::new();
+        let mut deserializer = toml::Deserializer::new(data);
+
+        let parsed = serde_ignored::deserialize(&mut deserializer, |path| {
+            unknown_keys.push(format_key_path(&path));
+        });
+
+        let mut toml_configuration: TomlConfig = match parsed.and_then(|c| deserializer.end().map(|_| c)) {
+            Ok(c) => c,
+            Err(e) => return Err(get_toml_error_details(data, &e))
+        };
+
+        toml_configuration.unknown_keys = unknown_keys;
 
         Ok(toml_configuration)
     }
 }
 
+/// Format a serde_ignored key path as a dotted TOML key, e.g.
+/// `plugins[1].foo`.
+fn format_key_path(path: &serde_ignored::Path) -> String {
+    match *path {
+        serde_ignored::Path::Root => String::new(),
+        serde_ignored::Path::Seq { parent, index } => {
+            format!("{}[{}]", format_key_path(parent), index)
+        }
+        serde_ignored::Path::Map { parent, ref key } => {
+            let parent_key = format_key_path(parent);
+
+            if parent_key.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", parent_key, key)
+            }
+        }
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => format_key_path(parent),
+    }
+}
+
+/// Get the matcher of TOML error messages, which are formatted as
+/// `<message> for key `<key>` at line <line>`, of which the key and the line
+/// are optional.
+fn get_toml_error_matcher() -> &'static Regex {
+    static MATCHER: OnceLock<Regex> = OnceLock::new();
+
+    MATCHER.get_or_init(|| Regex::new(r"(?s)^(.*?)(?: for key `([^`]*)`)?(?: at line \d+)?$").unwrap())
+}
+
+/// Turn a TOML parser error into a configuration error with the offending
+/// key and position, when those are known.
+fn get_toml_error_details(data: &str, error: &toml::de::Error) -> ConfigError {
+    let matcher = get_toml_error_matcher();
+    let error_msg = format!("{}", error);
+
+    let mut details = ConfigError::new(&error_msg);
+
+    if let Some(caps) = matcher.captures(&error_msg) {
+        details.message = caps.get(1).map_or(error_msg.clone(), |m| m.as_str().to_string());
+        details.key = caps.get(2).map(|m| m.as_str().to_string());
+    }
+
+    let position = match (error.line_col(), details.key.clone()) {
+        (Some(pos), _) => Some(pos),
+        (None, Some(key)) => find_key_position(data, &key),
+        (None, None) => None
+    };
+
+    if let Some((line, col)) = position {
+        details.line = Some(line + 1);
+        details.column = Some(col + 1);
+    }
+
+    details
+}
+
+/// Find the 0-based line and column of a dotted key inside TOML data. If the
+/// key points to a table the position of the table header is returned.
+fn find_key_position(data: &str, key: &str) -> Option<(usize, usize)> {
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let name = parts.pop()?;
+    let table = parts.join(".");
+
+    let mut current_table = String::new();
+
+    for (i, line) in data.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+
+        if trimmed.starts_with('[') {
+            current_table = trimmed
+                .trim_end()
+                .trim_matches(|c| c == '[' || c == ']')
+                .trim()
+                .to_string();
+
+            if current_table == key {
+                return Some((i, indent));
+            }
+
+            continue;
+        }
+
+        if current_table != table || !trimmed.starts_with(name) {
+            continue;
+        }
+
+        if trimmed[name.len()..].trim_start().starts_with('=') {
+            return Some((i, indent));
+        }
+    }
+
+    None
+}
+
+/// Resolve which configuration file to use. A given path is used as is,
+/// relative paths being resolved against the working directory. If no path is
+/// given the closest `wppr.toml` is searched for starting from the working
+/// directory and moving upwards.
+pub fn resolve_config_file(given: Option<String>, working_dir: &Path) -> Result<PathBuf> {
+    if let Some(path) = given {
+        return Ok(working_dir.join(path));
+    }
+
+    match find_config_file(working_dir) {
+        Some(file) => Ok(file),
+        None => Err(Error::Config(ConfigError::new(&format!(
+            "No configuration file given and no `{}` found in `{}` or its parent directories",
+            CONFIG_FILE_NAME,
+            working_dir.display()
+        ))))
+    }
+}
+
+/// Find the closest `wppr.toml` from a directory or its parent directories.
+pub fn find_config_file(start_dir: &Path) -> Option<PathBuf> {
+    start_dir.ancestors()
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .find(|file| file.is_file())
+}
+
+/// Find the root directory of a WordPress installation, i.e. the directory
+/// containing `wp-config.php`. The configuration directory is checked first,
+/// after which its subdirectories are searched a few levels deep.
+pub fn find_wordpress_root(config_dir: &Path) -> Option<PathBuf> {
+    let mut search_dirs: Vec<PathBuf> = vec![config_dir.to_path_buf()];
+
+    for _ in 0..WP_ROOT_SEARCH_DEPTH + 1 {
+        let mut next_dirs: Vec<PathBuf> = Vec::new();
+
+        for dir in search_dirs {
+            if dir.join("wp-config.php").is_file() {
+                return Some(dir);
+            }
+
+            let mut subdirs: Vec<PathBuf> = match read_dir(&dir) {
+                Ok(entries) => entries
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.path())
+                    .filter(|p| p.is_dir() && !is_ignored_search_dir(p))
+                    .collect(),
+                Err(_) => continue
+            };
+
+            subdirs.sort();
+            next_dirs.append(&mut subdirs);
+        }
+
+        search_dirs = next_dirs;
+    }
+
+    None
+}
+
+/// Directories which never contain a WordPress installation root and can be
+/// skipped when searching for one.
+fn is_ignored_search_dir(dir: &Path) -> bool {
+    match dir.file_name().and_then(|n| n.to_str()) {
+        Some(name) => {
+            name.starts_with('.')
+                || name == "wp-content"
+                || name == "wp-includes"
+                || name == "wp-admin"
+                || name == "node_modules"
+                || name == "vendor"
+        }
+        None => true
+    }
+}
+
 /// Validate configuration for the tool.
-pub fn validate_configuration(config: &TomlConfig) -> Result<bool, &'static str> {
+pub fn validate_configuration(config: &TomlConfig) -> Result<bool> {
     let bins: BinariesConfig = config.clone().binaries.unwrap_or(BinariesConfig::default());
 
-    let git_is_valid = validate_binary(&bins.git);
-    let wp_cli_is_valid = validate_binary(&bins.wpcli);
-
-    if !git_is_valid {
-        return Err("Invalid git binary provided");
-    } else if !wp_cli_is_valid {
-        return Err("Invalid wp cli binary provided");
+    let binaries = vec![
+        ("git", bins.git.clone()),
+        ("wp cli", bins.wpcli.clone()),
+        ("shell", bins.get_shell()),
+    ];
+
+    for (name, bin) in binaries {
+        if !validate_binary(&bin) {
+            return Err(Error::MissingBinary {
+                name: name.to_string(),
+                bin,
+            });
+        }
     }
 
     Ok(true)
@@ -136,22 +553,118 @@ pub struct RuntimeConfig {
     pub binaries: BinariesConfig,
     pub git: GitConfig,
     pub plugins: Vec<PluginConfig>,
+    pub pre_upgrade: HooksConfig,
+    pub post_upgrade: HooksConfig,
+    pub repository: RepositoryConfig,
+    pub backups: BackupsConfig,
     pub verbose: bool,
     pub dry_run: bool,
     pub cwd: PathBuf,
+    pub wp_root: Option<PathBuf>,
+    pub file: Option<PathBuf>,
 }
 
 impl RuntimeConfig {
-    pub fn from_toml_config(toml_config: TomlConfig) -> Result<RuntimeConfig, &'static str> {
+    /// Get the directory a plugin `index_path` is relative to. Paths are
+    /// resolved relative to the WordPress root if one was found and the
+    /// plugin exists there, otherwise relative to the configuration directory.
+    pub fn get_index_path_base(&self, plugin_config: &PluginConfig) -> PathBuf {
+        match self.wp_root {
+            Some(ref root) if root.join(&plugin_config.index_path).exists() => root.clone(),
+            _ => self.cwd.clone()
+        }
+    }
+
+    /// Get the directory release artifacts are stored in, if one has been
+    /// configured. Relative paths are relative to the configuration directory.
+    pub fn get_artifacts_dir(&self) -> Option<PathBuf> {
+        self.repository.artifacts_dir.as_ref().map(|d| self.cwd.join(d))
+    }
+
+    /// Get the directory the Composer repository is exported to when serving
+    /// it. Defaults to `.wpprrepository` in the configuration directory.
+    pub fn get_export_dir(&self) -> PathBuf {
+        match self.repository.export_dir {
+            Some(ref d) => self.cwd.join(d),
+            None => self.cwd.join(".wpprrepository")
+        }
+    }
+
+    /// Get the directory plugin mirror repositories are kept in, if one has
+    /// been configured. Relative paths are relative to the configuration
+    /// directory.
+    pub fn get_mirrors_dir(&self) -> Option<PathBuf> {
+        self.git.mirrors_dir.as_ref().map(|d| self.cwd.join(d))
+    }
+
+    /// Get the allowed signers file tags are verified against, if one has been
+    /// configured. Relative paths are relative to the configuration directory.
+    pub fn get_allowed_signers_file(&self) -> Option<PathBuf> {
+        self.git.allowed_signers.as_ref().map(|f| self.cwd.join(f))
+    }
+
+    /// Get the mirror repository directory of a plugin, `<mirrors_dir>/<slug>`,
+    /// if a mirrors directory has been configured.
+    pub fn get_mirror_dir(&self, plugin: &Plugin) -> Result<Option<PathBuf>> {
+        match self.get_mirrors_dir() {
+            Some(dir) => Ok(Some(dir.join(plugin.get_cli_name()?))),
+            None => Ok(None)
+        }
+    }
+
+    /// Get the directory the git repository of a plugin lives in, either its
+    /// mirror directory or the plugin directory itself.
+    pub fn get_repository_dir(&self, plugin: &Plugin) -> Result<PathBuf> {
+        Ok(self.get_mirror_dir(plugin)?.unwrap_or_else(|| plugin.get_plugin_dir()))
+    }
+
+    /// Get the directory plugin backups are stored in.
+    pub fn get_backups_dir(&self) -> PathBuf {
+        self.cwd.join(".wpprbackups")
+    }
+
+    /// Find the configuration of a managed plugin by plugin slug.
+    pub fn find_plugin_config(&self, slug: &str) -> Option<&PluginConfig> {
+        self.plugins.iter().find(|p| p.get_slug().as_deref() == Some(slug))
+    }
+
+    pub fn from_toml_config(toml_config: TomlConfig) -> Result<RuntimeConfig> {
         validate_configuration(&toml_config)?;
 
+        let git = match toml_config.git {
+            Some(g) => g,
+            None => {
+                let mut e = ConfigError::new("Missing required `[git]` section");
+                e.key = Some("git".to_string());
+                e.file = toml_config.file.clone();
+
+                return Err(Error::Config(e));
+            }
+        };
+
+        let cwd = match toml_config.cwd {
+            Some(c) => c,
+            None => {
+                let mut e = ConfigError::new("Configuration directory is unknown");
+                e.file = toml_config.file.clone();
+
+                return Err(Error::Config(e));
+            }
+        };
+
         Ok(RuntimeConfig {
             binaries: toml_config.binaries.unwrap_or(BinariesConfig::default()),
-            git: toml_config.git.unwrap(),
+            git,
             plugins: toml_config.plugins.unwrap_or(Vec::new()),
+            pre_upgrade: toml_config.pre_upgrade.unwrap_or_default(),
+            post_upgrade: toml_config.post_upgrade.unwrap_or_default(),
+            repository: toml_config.repository.unwrap_or_default(),
+            backups: toml_config.backups.unwrap_or_default(),
             verbose: toml_config.verbose.unwrap_or(false),
             dry_run: toml_config.dry_run.unwrap_or(false),
-            cwd: PathBuf::from(toml_config.cwd.unwrap()),
+            wp_root: find_wordpress_root(Path::new(&cwd)),
+            cwd: PathBuf::from(cwd),
+            file: toml_config.file,
         })
     }
 }
@@ -165,23 +678,43 @@ mod tests {
             binaries: Some(BinariesConfig {
                 git: "/bin/true".to_string(),
                 wpcli: "/bin/true".to_string(),
+                shell: None,
             }),
             plugins: Some(vec![
                 PluginConfig {
                     index_path: "".to_string(),
                     remote_repository: "".to_string(),
                     package_name: "".to_string(),
-                    pre_cmds: None
+                    pre_cmds: None,
+                    post_cmds: None,
+                    cmds_timeout: None,
+                    composer: None,
+                    composer_strategy: None,
+                    hold: None,
+                    allow: None,
+                    max_version: None
                 },
             ]),
             git: Some(GitConfig {
                 user_email: "".to_string(),
                 user_name: "".to_string(),
                 force_push: false,
+                backend: None,
+                mirrors_dir: None,
+                commit_message: None,
+                sign: None,
+                signing_key: None,
+                allowed_signers: None,
             }),
+            pre_upgrade: None,
+            post_upgrade: None,
+            repository: None,
+            backups: None,
             verbose: Some(false),
             dry_run: Some(false),
             cwd: Some("".to_string()),
+            file: None,
+            unknown_keys: Vec::new(),
         };
 
         testcfg
@@ -192,23 +725,43 @@ mod tests {
             binaries: Some(BinariesConfig {
                 git: "/bin/true".to_string(),
                 wpcli: "/this/should/not/work".to_string(),
+                shell: None,
             }),
             plugins: Some(vec![
                 PluginConfig {
                     index_path: "".to_string(),
                     remote_repository: "".to_string(),
                     package_name: "".to_string(),
-                    pre_cmds: None
+                    pre_cmds: None,
+                    post_cmds: None,
+                    cmds_timeout: None,
+                    composer: None,
+                    composer_strategy: None,
+                    hold: None,
+                    allow: None,
+                    max_version: None
                 },
             ]),
             git: Some(GitConfig {
                 user_email: "".to_string(),
                 user_name: "".to_string(),
                 force_push: false,
+                backend: None,
+                mirrors_dir: None,
+                commit_message: None,
+                sign: None,
+                signing_key: None,
+                allowed_signers: None,
             }),
+            pre_upgrade: None,
+            post_upgrade: None,
+            repository: None,
+            backups: None,
             verbose: Some(false),
             dry_run: Some(false),
             cwd: Some("".to_string()),
+            file: None,
+            unknown_keys: Vec::new(),
         };
 
         testcfg
@@ -0,0 +1,194 @@
+//! # config_editor
+//!
+//! Editing of configuration files in place. Unlike `TomlConfig`, which is
+//! only ever read, the editor keeps comments, formatting and ordering of the
+//! configuration file intact.
+
+use std::fs::{read_to_string, write};
+use std::path::{Path, PathBuf};
+
+use toml;
+use toml_edit::{value, Array, ArrayOfTables, DocumentMut, Item, Table};
+
+use config::{PluginConfig, PluginHold};
+use error::{ConfigError, Error, Result};
+
+/// Editable configuration file.
+pub struct ConfigEditor {
+    file: PathBuf,
+    document: DocumentMut,
+}
+
+impl ConfigEditor {
+    /// Open a configuration file for editing.
+    pub fn open(file: &Path) -> Result<ConfigEditor> {
+        let contents = match read_to_string(file) {
+            Ok(c) => c,
+            Err(e) => {
+                return Err(Error::Config(
+                    ConfigError::new(&format!("Could not read configuration file: {}", e)).with_file(file)
+                ));
+            }
+        };
+
+        let document = match contents.parse::<DocumentMut>() {
+            Ok(d) => d,
+            Err(e) => {
+                return Err(Error::Config(
+                    ConfigError::new(e.message()).with_file(file)
+                ));
+            }
+        };
+
+        Ok(ConfigEditor {
+            file: file.to_path_buf(),
+            document,
+        })
+    }
+
+    /// Get the `[[plugins]]` entries of the configuration, creating them if
+    /// none exist yet.
+    fn get_plugins_mut(&mut self) -> Result<&mut ArrayOfTables> {
+        let file = self.file.clone();
+        let plugins = self
+            .document
+            .entry("plugins")
+            .or_insert_with(|| Item::ArrayOfTables(ArrayOfTables::new()));
+
+        match plugins.as_array_of_tables_mut() {
+            Some(p) => Ok(p),
+            None => {
+                let mut e = ConfigError::new("Plugins must be defined as `[[plugins]]` entries");
+                e.key = Some("plugins".to_string());
+                e.file = Some(file);
+
+                Err(Error::Config(e))
+            }
+        }
+    }
+
+    /// Append a plugin entry after all existing plugin entries.
+    pub fn add_plugin(&mut self, plugin_config: &PluginConfig) -> Result<()> {
+        let mut table = Table::new();
+
+        table["index_path"] = value(plugin_config.index_path.clone());
+        table["package_name"] = value(plugin_config.package_name.clone());
+        table["remote_repository"] = value(plugin_config.remote_repository.clone());
+
+        if let Some(ref cmds) = plugin_config.pre_cmds {
+            table["pre_cmds"] = value(cmds.iter().collect::<Array>());
+        }
+
+        if let Some(ref cmds) = plugin_config.post_cmds {
+            table["post_cmds"] = value(cmds.iter().collect::<Array>());
+        }
+
+        if let Some(timeout) = plugin_config.cmds_timeout {
+            table["cmds_timeout"] = value(timeout as i64);
+        }
+
+        if let Some(strategy) = plugin_config.composer_strategy {
+            table["composer_strategy"] = value(strategy.as_str());
+        }
+
+        match plugin_config.hold {
+            Some(PluginHold::Enabled(held)) => table["hold"] = value(held),
+            Some(PluginHold::Version(ref version)) => table["hold"] = value(version.clone()),
+            None => ()
+        };
+
+        if let Some(allow) = plugin_config.allow {
+            table["allow"] = value(allow.as_str());
+        }
+
+        if let Some(ref max_version) = plugin_config.max_version {
+            table["max_version"] = value(max_version.clone());
+        }
+
+        if let Some(ref composer) = plugin_config.composer {
+            table["composer"] = get_composer_item(composer)?;
+        }
+
+        self.get_plugins_mut()?.push(table);
+
+        Ok(())
+    }
+
+    /// Remove the plugin entry with the given index path. Returns whether an
+    /// entry was removed.
+    pub fn remove_plugin(&mut self, index_path: &str) -> Result<bool> {
+        let plugins = self.get_plugins_mut()?;
+
+        let position = plugins
+            .iter()
+            .position(|t| t.get("index_path").and_then(|i| i.as_str()) == Some(index_path));
+
+        match position {
+            Some(i) => {
+                plugins.remove(i);
+
+                Ok(true)
+            }
+            None => Ok(false)
+        }
+    }
+
+    /// Set a string value of the plugin entry with the given index path.
+    /// Returns whether a matching entry was found.
+    pub fn set_plugin_value(&mut self, index_path: &str, key: &str, new_value: &str) -> Result<bool> {
+        let plugins = self.get_plugins_mut()?;
+
+        let plugin = plugins
+            .iter_mut()
+            .find(|t| t.get("index_path").and_then(|i| i.as_str()) == Some(index_path));
+
+        match plugin {
+            Some(table) => {
+                table[key] = value(new_value);
+
+                Ok(true)
+            }
+            None => Ok(false)
+        }
+    }
+
+    /// Get the edited configuration as a string.
+    pub fn to_toml_string(&self) -> String {
+        self.document.to_string()
+    }
+
+    /// Write the edited configuration back to its file.
+    pub fn save(&self) -> Result<()> {
+        write(&self.file, self.to_toml_string())?;
+
+        Ok(())
+    }
+}
+
+/// Convert a composer configuration table into an editable `[composer]`
+/// table.
+fn get_composer_item(composer: &toml::value::Table) -> Result<Item> {
+    let mut root = toml::value::Table::new();
+
+    root.insert("composer".to_string(), toml::Value::Table(composer.clone()));
+
+    let contents = match toml::to_string(&toml::Value::Table(root)) {
+        Ok(c) => c,
+        Err(e) => {
+            let mut error = ConfigError::new(&format!("Cannot write composer configuration: {}", e));
+            error.key = Some("composer".to_string());
+
+            return Err(Error::Config(error));
+        }
+    };
+
+    match contents.parse::<DocumentMut>() {
+        Ok(mut d) => Ok(d.remove("composer").unwrap_or(Item::None)),
+        Err(e) => {
+            let mut error = ConfigError::new(e.message());
+            error.key = Some("composer".to_string());
+
+            Err(Error::Config(error))
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! # error
+//!
+//! Error type shared by all modules of this crate.
+
+use serde_json;
+
+use std::{
+    error,
+    fmt,
+    io,
+    path::{Path, PathBuf},
+    result
+};
+
+/// All errors that can occur when running the tool.
+#[derive(Debug)]
+pub enum Error {
+    /// Configuration file could not be read, parsed, or contains invalid
+    /// values.
+    Config(ConfigError),
+    /// A binary required by the tool could not be run.
+    MissingBinary { name: String, bin: String },
+    /// A git command exited unsuccessfully.
+    Git { command: String, code: Option<i32>, stderr: String },
+    /// A WP-CLI command exited unsuccessfully.
+    WpCli { command: String, code: Option<i32>, stderr: String },
+    /// A user defined shell command failed or timed out.
+    Shell { command: String, code: Option<i32>, stderr: String, timed_out: bool },
+    /// A plugin version could not be read or parsed.
+    Version(String),
+    /// A plugin is invalid or cannot be managed.
+    Plugin(String),
+    /// Creating or restoring a plugin backup failed.
+    Backup(String),
+    /// Composer repository server could not be started.
+    Server(String),
+    /// JSON could not be parsed or serialized.
+    Json(serde_json::Error),
+    /// Underlying IO error.
+    Io(io::Error),
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+/// Diagnostic details of an invalid configuration. Line and column numbers
+/// are 1-based.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigError {
+    pub file: Option<PathBuf>,
+    pub key: Option<String>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl ConfigError {
+    /// Get a new configuration error with a message only.
+    pub fn new(message: &str) -> Self {
+        ConfigError {
+            message: message.to_string(),
+            ..ConfigError::default()
+        }
+    }
+
+    /// Set the configuration file the error originates from.
+    pub fn with_file(mut self, file: &Path) -> Self {
+        self.file = Some(file.to_path_buf());
+        self
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid configuration")?;
+
+        if let Some(ref file) = self.file {
+            write!(f, " in `{}`", file.display())?;
+        }
+
+        match (self.line, self.column) {
+            (Some(l), Some(c)) => write!(f, " at line {}, column {}", l, c)?,
+            (Some(l), None) => write!(f, " at line {}", l)?,
+            _ => ()
+        };
+
+        if let Some(ref key) = self.key {
+            write!(f, " for key `{}`", key)?;
+        }
+
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Format an optional exit code for error messages.
+fn fmt_code(code: &Option<i32>) -> String {
+    match *code {
+        Some(c) => format!("exit code {}", c),
+        None => "no exit code".to_string(),
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Config(ref e) => write!(f, "{}", e),
+            Error::MissingBinary { ref name, ref bin } => {
+                write!(f, "Invalid {} binary provided: `{}`", name, bin)
+            }
+            Error::Git { ref command, ref code, ref stderr } => write!(
+                f,
+                "Git command `{}` failed with {}: `{}`",
+                command,
+                fmt_code(code),
+                stderr.trim()
+            ),
+            Error::WpCli { ref command, ref code, ref stderr } => write!(
+                f,
+                "WP-CLI command `{}` failed with {}: `{}`",
+                command,
+                fmt_code(code),
+                stderr.trim()
+            ),
+            Error::Shell { ref command, timed_out: true, .. } => {
+                write!(f, "Command `{}` timed out", command)
+            }
+            Error::Shell { ref command, ref code, ref stderr, .. } => write!(
+                f,
+                "Command `{}` failed with {}: `{}`",
+                command,
+                fmt_code(code),
+                stderr.trim()
+            ),
+            Error::Version(ref msg) => write!(f, "Invalid plugin version: {}", msg),
+            Error::Plugin(ref msg) => write!(f, "{}", msg),
+            Error::Backup(ref msg) => write!(f, "{}", msg),
+            Error::Server(ref msg) => write!(f, "Repository server error: {}", msg),
+            Error::Json(ref e) => write!(f, "JSON error: {}", e),
+            Error::Io(ref e) => write!(f, "IO error: {}", e),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Json(ref e) => Some(e),
+            Error::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
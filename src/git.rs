@@ -1,13 +1,21 @@
 //! # git
 //!
-//! This module contains Git specific functionalities.
+//! This module contains Git specific functionalities. Git operations go
+//! through the `VcsBackend` trait, which is implemented by a wrapper for the
+//! git binary and by a native libgit2 implementation.
 
 use std::{
-    path::PathBuf,
-    process::Command
+    io,
+    path::{Path, PathBuf},
+    process::{Command, Output}
 };
 
-use config::GitConfig;
+use config::{GitBackend, GitConfig};
+use error::{Error, Result};
+
+mod libgit2;
+
+pub use self::libgit2::LibGit2;
 
 /// Wrapper for Git.
 pub struct Git {
@@ -16,7 +24,243 @@ pub struct Git {
     working_directory: PathBuf
 }
 
-pub type GitResult = Result<bool, String>;
+pub type GitResult = Result<bool>;
+
+/// Commit message of commits which are not upgrades, e.g. the initial commit
+/// of a plugin.
+pub const AUTOMATED_COMMIT_MESSAGE: &str = "Automated commit by wppr";
+
+/// Render a commit message template. `{plugin}`, `{old_version}`,
+/// `{new_version}`, and `{date}` are replaced with their values.
+///
+/// # Example
+///
+/// ```
+/// use wppr::git::render_commit_message;
+///
+/// let message = render_commit_message("Update {plugin} to {new_version} ({date})", "my-plugin", "1.0", "1.1", "2024-01-31");
+///
+/// assert_eq!("Update my-plugin to 1.1 (2024-01-31)", message);
+/// ```
+pub fn render_commit_message(template: &str, plugin: &str, old_version: &str, new_version: &str, date: &str) -> String {
+    template
+        .replace("{plugin}", plugin)
+        .replace("{old_version}", old_version)
+        .replace("{new_version}", new_version)
+        .replace("{date}", date)
+}
+
+/// How a file differs from the last commit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileStatus {
+    Added,
+    Modified,
+    Deleted,
+    Untracked,
+}
+
+impl FileStatus {
+    /// Get the status as it is shown in change summaries.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            FileStatus::Added => "added",
+            FileStatus::Modified => "modified",
+            FileStatus::Deleted => "deleted",
+            FileStatus::Untracked => "untracked",
+        }
+    }
+}
+
+/// An uncommitted change of a single file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileChange {
+    /// Path of the file relative to the repository root.
+    pub path: String,
+    pub status: FileStatus,
+}
+
+/// Parse the output of `git status --porcelain=v1 -z`. Renamed and copied
+/// files are reported as added, as their original path is kept in the
+/// repository by the copy or removed by a separate entry.
+///
+/// # Example
+///
+/// ```
+/// use wppr::git::{parse_porcelain_status, FileChange, FileStatus};
+///
+/// let changes = parse_porcelain_status(" M plugin.php\0?? new file.js\0D  old.css\0");
+///
+/// assert_eq!(3, changes.len());
+/// assert_eq!(FileChange { path: "plugin.php".to_string(), status: FileStatus::Modified }, changes[0]);
+/// assert_eq!(FileStatus::Untracked, changes[1].status);
+/// assert_eq!("new file.js", changes[1].path);
+/// assert_eq!(FileStatus::Deleted, changes[2].status);
+/// ```
+pub fn parse_porcelain_status(output: &str) -> Vec<FileChange> {
+    let mut changes = Vec::new();
+    let mut entries = output.split('\0').filter(|e| !e.is_empty());
+
+    while let Some(entry) = entries.next() {
+        if entry.len() < 4 {
+            continue;
+        }
+
+        let (code, path) = entry.split_at(3);
+        let mut code = code.chars();
+        let index = code.next().unwrap_or(' ');
+        let worktree = code.next().unwrap_or(' ');
+
+        // renames and copies are followed by the original path
+        if index == 'R' || index == 'C' {
+            entries.next();
+        }
+
+        let status = match (index, worktree) {
+            ('!', '!') => continue,
+            ('?', '?') => FileStatus::Untracked,
+            ('D', _) | (_, 'D') => FileStatus::Deleted,
+            ('A', _) | ('R', _) | ('C', _) => FileStatus::Added,
+            _ => FileStatus::Modified,
+        };
+
+        changes.push(FileChange {
+            path: path.to_string(),
+            status
+        });
+    }
+
+    changes
+}
+
+/// Get a summary of uncommitted changes with a line for each file. Untracked
+/// files are summarized as added, as committing adds them.
+///
+/// # Example
+///
+/// ```
+/// use wppr::git::{get_change_summary, FileChange, FileStatus};
+///
+/// let summary = get_change_summary(&[
+///     FileChange { path: "plugin.php".to_string(), status: FileStatus::Modified },
+///     FileChange { path: "new.js".to_string(), status: FileStatus::Untracked },
+/// ]);
+///
+/// assert_eq!("2 files changed: 1 added, 1 modified, 0 deleted\n\nmodified: plugin.php\nadded: new.js", summary);
+/// ```
+pub fn get_change_summary(changes: &[FileChange]) -> String {
+    let get_status = |change: &FileChange| match change.status {
+        FileStatus::Untracked => FileStatus::Added,
+        status => status,
+    };
+    let count = |status: FileStatus| changes.iter().filter(|c| get_status(c) == status).count();
+
+    let mut summary = format!(
+        "{} {} changed: {} added, {} modified, {} deleted\n",
+        changes.len(),
+        if changes.len() == 1 { "file" } else { "files" },
+        count(FileStatus::Added),
+        count(FileStatus::Modified),
+        count(FileStatus::Deleted)
+    );
+
+    for change in changes {
+        summary.push_str(&format!("\n{}: {}", get_status(change).as_str(), change.path));
+    }
+
+    summary
+}
+
+/// Version control operations wppr needs for managing plugin repositories.
+pub trait VcsBackend {
+    /// Get a backend of the same kind and configuration for another working
+    /// directory.
+    fn with_working_directory(&self, cwd: PathBuf) -> Box<dyn VcsBackend>;
+
+    /// Set the name and email config for a git repo.
+    fn set_repo_git_config(&self) -> GitResult;
+
+    /// See if a git repository is initialized.
+    fn repository_is_initialized(&self) -> GitResult;
+
+    /// Initialize a new git repository.
+    fn initialize_repository(&self) -> GitResult;
+
+    /// Get the uncommitted changes of the working tree, including untracked
+    /// files.
+    fn get_status(&self) -> Result<Vec<FileChange>>;
+
+    /// Does a repo have any uncommitted changes?
+    fn has_uncommited_changes(&self) -> GitResult {
+        Ok(!self.get_status()?.is_empty())
+    }
+
+    /// If there are uncommited changes in a repo, add and commit them with a
+    /// message.
+    fn add_and_commit_changes(&self, message: &str) -> GitResult;
+
+    /// Add the `wppr` remote repository. An existing remote is not an error.
+    fn add_remote(&self, remote_uri: String) -> GitResult;
+
+    /// Push `master` and tags to the `wppr` remote repository.
+    fn push_to_remote(&self) -> GitResult;
+
+    /// Add a new annotated git tag with a message to the repository.
+    fn add_tag(&self, tag: String, message: &str) -> GitResult;
+
+    /// Delete a tag from the repository.
+    fn delete_tag(&self, tag: &str) -> GitResult;
+
+    /// Does a tag exist in the repository?
+    fn tag_exists(&self, tag: &str) -> GitResult;
+
+    /// List all tags of the repository.
+    fn list_tags(&self) -> Result<Vec<String>>;
+
+    /// Get the commit hash a revision, e.g. a tag, points to.
+    fn get_commit_hash(&self, rev: &str) -> Result<String>;
+
+    /// Get the commit time of a revision in ISO 8601 format.
+    fn get_commit_time(&self, rev: &str) -> Result<String>;
+
+    /// Get the contents of a file at a revision. Returns `None` if the file
+    /// does not exist at that revision.
+    fn get_file_at(&self, rev: &str, path: &str) -> Result<Option<String>>;
+
+    /// Write a zip archive of the tree at a revision with all entries placed
+    /// under `prefix`.
+    fn archive_zip(&self, rev: &str, prefix: &str, dest: &Path) -> GitResult;
+
+    /// Replace the index and the tracked files of the working tree with the
+    /// tree of a revision, keeping the current branch checked out.
+    fn checkout_tree(&self, rev: &str) -> GitResult;
+
+    /// Are there changes in the index which have not been committed?
+    fn has_staged_changes(&self) -> GitResult;
+
+    /// Commit the changes in the index with a message.
+    fn commit_staged(&self, message: &str) -> GitResult;
+
+    /// Reset plugin contents to the last committed state.
+    fn reset_contents(&self) -> GitResult;
+
+    /// Reset the current branch, index, and plugin contents to a revision,
+    /// dropping the commits made after it.
+    fn reset_to(&self, rev: &str) -> GitResult;
+
+    /// Verify the signature of a tag against an SSH allowed signers file.
+    /// Returns `None` if the tag is signed by a trusted key, or the reason it
+    /// could not be verified otherwise.
+    fn verify_tag(&self, tag: &str, allowed_signers: &Path) -> Result<Option<String>>;
+}
+
+/// Get the git backend selected in the configuration for a working directory.
+/// `bin` is the git binary, which the libgit2 backend also uses for archives.
+pub fn get_backend(bin: String, config: GitConfig, cwd: PathBuf) -> Box<dyn VcsBackend> {
+    match config.backend.unwrap_or_default() {
+        GitBackend::Cli => Box::new(Git::new(bin, config, cwd)),
+        GitBackend::Libgit2 => Box::new(LibGit2::new(bin, config, cwd)),
+    }
+}
 
 impl Git {
     /// Get a new git wrapper instance.
@@ -40,204 +284,277 @@ impl Git {
         return cmd;
     }
 
-    /// Set the name and email config for a git repo.
-    pub fn set_repo_git_config(&self) -> GitResult {
-        let mut name_cmd = self.get_base_cmd();
+    /// Run a git command with the given arguments and return its output,
+    /// regardless of the exit status.
+    fn run_cmd(&self, args: &[&str]) -> Result<Output> {
+        let mut cmd = self.get_base_cmd();
 
-        name_cmd.args(&[
-            "config",
-            "user.name",
-            &format!("\"{}\"", self.config.user_name)
-        ]);
+        cmd.args(args);
 
-        let mut email_cmd = self.get_base_cmd();
+        match cmd.output() {
+            Ok(output) => Ok(output),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Err(Error::MissingBinary {
+                name: "git".to_string(),
+                bin: self.bin.clone()
+            }),
+            Err(e) => Err(Error::Io(e))
+        }
+    }
 
-        email_cmd.args(&[
-            "config",
-            "user.name",
-            &format!("\"{}\"", self.config.user_email)
-        ]);
+    /// Run a git command with the given arguments, erroring if the command
+    /// exits unsuccessfully.
+    fn run_checked_cmd(&self, args: &[&str]) -> Result<Output> {
+        let output = self.run_cmd(args)?;
 
-        let _ = name_cmd.output().expect("Error when trying to run git command: `git config user.name ...`");
-        let _ = email_cmd.output().expect("Error when trying to run git command: `git config user.email ...`");
+        if output.status.success() {
+            return Ok(output);
+        }
 
-        return Ok(true);
+        Err(self.get_cmd_error(args, &output))
     }
 
-    /// See if a git repository is initialized.
-    pub fn repository_is_initialized(&self) -> GitResult {
-        let mut cmd = self.get_base_cmd();
+    /// Get the `-c` options selecting the configured signature format and
+    /// signing key. Empty if signing is not enabled.
+    fn get_signing_options(&self) -> Vec<String> {
+        let format = match self.config.sign {
+            Some(f) => f,
+            None => return Vec::new()
+        };
 
-        cmd.arg("status");
+        let mut options = vec!["-c".to_string(), format!("gpg.format={}", format.get_gpg_format())];
 
-        let output = cmd.output().expect("Error when trying to run git command: `git status`");
+        if let Some(ref key) = self.config.signing_key {
+            options.push("-c".to_string());
+            options.push(format!("user.signingkey={}", key));
+        }
 
-        // exit code 0 usually means we are in fact inside a repo directory
-        return Ok(output.status.success());
+        options
     }
 
-    /// Initialize a new git repository.
-    pub fn initialize_repository(&self) -> GitResult {
-        let mut cmd = self.get_base_cmd();
+    /// Run a git command which creates a commit or a tag. If signing is
+    /// enabled, `sign_flag` is added after the subcommand.
+    fn run_signing_cmd(&self, args: &[&str], sign_flag: &str) -> Result<Output> {
+        let options = self.get_signing_options();
+        let mut signing_args: Vec<&str> = options.iter().map(|o| o.as_str()).collect();
+
+        signing_args.push(args[0]);
+
+        if self.config.sign.is_some() {
+            signing_args.push(sign_flag);
+        }
 
-        cmd.args(&["init", "."]);
+        signing_args.extend_from_slice(&args[1..]);
 
-        let output = cmd.output().expect("Error when trying to run git command: `git init .`");
+        self.run_checked_cmd(&signing_args)
+    }
 
-        match output.status.success() {
-            true => {
-                self.set_repo_git_config()
-            },
-            false => {
-                Err(format!("Could not initialize new git repository: `{}`", String::from_utf8_lossy(&output.stderr)))
-            }
+    /// Create an error for a failed git command.
+    fn get_cmd_error(&self, args: &[&str], output: &Output) -> Error {
+        Error::Git {
+            command: format!("git {}", args.join(" ")),
+            code: output.status.code(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string()
         }
     }
+}
 
-    /// Does a repo have any uncommitted changes?
-    pub fn has_uncommited_changes(&self) -> GitResult {
-        let mut cmd = self.get_base_cmd();
+impl VcsBackend for Git {
+    fn with_working_directory(&self, cwd: PathBuf) -> Box<dyn VcsBackend> {
+        Box::new(Git::new(self.bin.clone(), self.config.clone(), cwd))
+    }
 
-        cmd.arg("status");
+    fn set_repo_git_config(&self) -> GitResult {
+        self.run_checked_cmd(&["config", "user.name", &self.config.user_name])?;
+        self.run_checked_cmd(&["config", "user.email", &self.config.user_email])?;
 
-        let output = cmd.output().expect("Error when trying to run git command: `git status`");
+        return Ok(true);
+    }
 
-        match output.status.success() {
-            false => Err(format!("Could not check git repo status: `{}`", String::from_utf8_lossy(&output.stderr))),
-            true => {
-                let stdout = String::from_utf8_lossy(&output.stdout);
+    fn repository_is_initialized(&self) -> GitResult {
+        let output = self.run_cmd(&["rev-parse", "--show-toplevel"])?;
 
-                if stdout.contains("nothing to commit, working tree clean") {
-                    // no changes
-                    Ok(false)
-                } else {
-                    Ok(true)
-                }
-            }
+        // fails outside repos and inside the `.git` directory, but succeeds in
+        // any directory of a parent repository
+        if !output.status.success() {
+            return Ok(false);
         }
+
+        let toplevel = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim_end_matches('\n'));
+
+        Ok(toplevel.canonicalize().ok() == self.working_directory.canonicalize().ok())
+    }
+
+    fn initialize_repository(&self) -> GitResult {
+        self.run_checked_cmd(&["init", "."])?;
+
+        self.set_repo_git_config()
+    }
+
+    fn get_status(&self) -> Result<Vec<FileChange>> {
+        let output = self.run_checked_cmd(&["status", "--porcelain=v1", "-z", "--untracked-files=all"])?;
+
+        Ok(parse_porcelain_status(&String::from_utf8_lossy(&output.stdout)))
     }
 
-    /// If there are uncommited changes in a repo, add and commit them using a
-    /// generic commit message.
-    pub fn add_and_commit_changes(&self) -> GitResult {
+    fn add_and_commit_changes(&self, message: &str) -> GitResult {
         if self.has_uncommited_changes()? == false {
             return Ok(false);
         }
 
-        let mut cmd = self.get_base_cmd();
-
-        cmd.args(&["add", "."]);
+        self.run_checked_cmd(&["add", "."])?;
+        self.run_signing_cmd(&["commit", "-m", message], "-S")?;
 
-        let output = cmd.output().expect("Error when trying to run git command: `git add .`");
+        Ok(true)
+    }
 
-        match output.status.success() {
-            true => (),
-            false => {
-                return Err(format!("Could not read repository status: `{}`", String::from_utf8_lossy(&output.stderr)));
-            }
-        };
+    fn add_remote(&self, remote_uri: String) -> GitResult {
+        let args = ["remote", "add", "wppr", &remote_uri];
+        let output = self.run_cmd(&args)?;
 
-        let mut commit_cmd = self.get_base_cmd();
+        if output.status.success() || String::from_utf8_lossy(&output.stderr).contains("already exists") {
+            return Ok(true);
+        }
 
-        commit_cmd.args(&[
-            "commit",
-            "-m",
-            &format!("\"{}\"", "Automated commit by wppr")
-        ]);
+        Err(self.get_cmd_error(&args, &output))
+    }
 
-        let commit_output = commit_cmd.output().expect("Error when trying to run git command: `git commit -m ...`");
+    fn push_to_remote(&self) -> GitResult {
+        let mut args = vec!["push", "wppr", "master", "--follow-tags"];
 
-        match commit_output.status.success() {
-            true => Ok(true),
-            false => {
-                Err(format!("Could not commit changes: `{}`", String::from_utf8_lossy(&output.stderr)))
-            }
+        if self.config.force_push {
+            args.push("--force");
         }
+
+        self.run_checked_cmd(&args)?;
+
+        Ok(true)
     }
 
-    /// Add a remote repository.
-    pub fn add_remote(&self, remote_uri: String) -> GitResult {
-        let mut cmd = self.get_base_cmd();
+    fn add_tag(&self, tag: String, message: &str) -> GitResult {
+        self.run_signing_cmd(&["tag", "-a", &tag, "-m", message], "-s")?;
+
+        Ok(true)
+    }
 
-        cmd.args(&[
-            "remote",
-            "add",
-            "wppr",
-            &remote_uri,
-        ]);
+    fn delete_tag(&self, tag: &str) -> GitResult {
+        self.run_checked_cmd(&["tag", "-d", tag])?;
 
-        let output = cmd.output().expect("Error when trying to run git command: `git remote add wppr ...`");
+        Ok(true)
+    }
 
-        match output.status.success() {
-            true => Ok(true),
-            false => {
-                let stderr = String::from_utf8_lossy(&output.stderr);
+    fn tag_exists(&self, tag: &str) -> GitResult {
+        let output = self.run_cmd(&["rev-parse", "--verify", "--quiet", &format!("refs/tags/{}", tag)])?;
 
-                if stderr.contains("already exists") {
-                    Ok(true)
-                } else {
-                    Err(format!("Could not add remote repository: `{}`", stderr))
-                }
-            }
-        }
+        Ok(output.status.success())
     }
 
-    /// Push changes to a remote repository.
-    pub fn push_to_remote(&self) -> GitResult {
-        let mut cmd = self.get_base_cmd();
+    fn list_tags(&self) -> Result<Vec<String>> {
+        let output = self.run_checked_cmd(&["tag", "--list"])?;
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect())
+    }
 
-        cmd.args(&["push", "wppr", "master", "--follow-tags"]);
+    fn get_commit_hash(&self, rev: &str) -> Result<String> {
+        let output = self.run_checked_cmd(&["rev-parse", "--verify", &format!("{}^{{commit}}", rev)])?;
 
-        if self.config.force_push {
-            cmd.arg("--force");
-        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
 
-        let output = cmd.output()
-            .expect("Error when trying to run git command: `git push wppr master`");
+    fn get_commit_time(&self, rev: &str) -> Result<String> {
+        let output = self.run_checked_cmd(&["log", "-1", "--format=%cI", rev])?;
 
-        match output.status.success() {
-            true => Ok(true),
-            false => {
-                Err(format!(
-                    "Could not push to remote repository: `{}`",
-                    String::from_utf8_lossy(&output.stderr)
-                ))
-            }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn get_file_at(&self, rev: &str, path: &str) -> Result<Option<String>> {
+        let spec = format!("{}:{}", rev, path);
+
+        if !self.run_cmd(&["cat-file", "-e", &spec])?.status.success() {
+            return Ok(None);
         }
+
+        let output = self.run_checked_cmd(&["show", &spec])?;
+
+        Ok(Some(String::from_utf8_lossy(&output.stdout).to_string()))
     }
 
-    /// Add a new git tag to the repository
-    pub fn add_tag(&self, tag: String) -> GitResult {
-        let mut cmd = self.get_base_cmd();
+    /// Write a zip archive of the tree at a revision. All archive entries are
+    /// placed under `prefix`, and their modification times are set to the
+    /// commit time, so archiving the same revision always produces the same
+    /// archive.
+    fn archive_zip(&self, rev: &str, prefix: &str, dest: &Path) -> GitResult {
+        let dest = dest.to_string_lossy();
+
+        self.run_checked_cmd(&[
+            "archive",
+            "--format=zip",
+            &format!("--prefix={}/", prefix),
+            "-o",
+            &dest,
+            rev
+        ])?;
+
+        Ok(true)
+    }
+
+    fn checkout_tree(&self, rev: &str) -> GitResult {
+        self.run_checked_cmd(&["read-tree", "-u", "--reset", rev])?;
 
-        cmd.args(&["tag", &tag]);
+        Ok(true)
+    }
 
-        let output = cmd.output().expect("Error when trying to run git command: `git tag ...`");
+    fn has_staged_changes(&self) -> GitResult {
+        let output = self.run_cmd(&["diff", "--cached", "--quiet"])?;
 
-        match output.status.success() {
-            true => Ok(true),
-            false => {
-                Err(format!("Could not add git tag: `{}`", String::from_utf8_lossy(&output.stderr)))
-            }
+        match output.status.code() {
+            Some(0) => Ok(false),
+            Some(1) => Ok(true),
+            _ => Err(self.get_cmd_error(&["diff", "--cached", "--quiet"], &output))
         }
     }
 
-    pub fn reset_contents(&self) -> GitResult {
-        let mut cmd = self.get_base_cmd();
+    fn commit_staged(&self, message: &str) -> GitResult {
+        self.run_signing_cmd(&["commit", "-m", message], "-S")?;
+
+        Ok(true)
+    }
 
-        cmd.args(&["reset", "--hard"]);
+    fn reset_contents(&self) -> GitResult {
+        self.run_checked_cmd(&["reset", "--hard"])?;
 
-        let output = cmd.output()
-            .expect("Error when trying to run git command: `git reset --hard`");
+        Ok(true)
+    }
+
+    fn reset_to(&self, rev: &str) -> GitResult {
+        self.run_checked_cmd(&["reset", "--hard", rev])?;
+
+        Ok(true)
+    }
 
-        match output.status.success() {
-            true => Ok(true),
-            false => {
-                Err(format!(
-                    "Could not reset plugin contents to previous state: `{}`",
-                    String::from_utf8_lossy(&output.stderr)
-                ))
-            }
+    /// Verify a tag with `git verify-tag`. SSH signatures must be made by a
+    /// key in the allowed signers file, GPG signatures by a key which is
+    /// fully trusted in the GPG keyring.
+    fn verify_tag(&self, tag: &str, allowed_signers: &Path) -> Result<Option<String>> {
+        let signers = format!("gpg.ssh.allowedSignersFile={}", allowed_signers.display());
+        let output = self.run_cmd(&["-c", &signers, "-c", "gpg.minTrustLevel=fully", "verify-tag", tag])?;
+
+        if output.status.success() {
+            return Ok(None);
         }
+
+        // the reason is the last line git or the signing program prints
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr
+            .lines()
+            .rev()
+            .map(|l| l.trim().trim_start_matches("error: "))
+            .find(|l| !l.is_empty())
+            .unwrap_or("signature could not be verified");
+
+        Ok(Some(reason.to_string()))
     }
-}
\ No newline at end of file
+}
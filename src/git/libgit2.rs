@@ -0,0 +1,392 @@
+//! # libgit2
+//!
+//! Git backend which uses libgit2 instead of the git binary. Archives,
+//! signed commits and tags, and signature verification are left to the git
+//! binary, as libgit2 cannot create or verify signatures by itself.
+
+use std::{
+    cell::Cell,
+    path::{Path, PathBuf}
+};
+
+use chrono::{FixedOffset, SecondsFormat, TimeZone};
+use git2::{
+    build::CheckoutBuilder, ConfigLevel, Cred, CredentialType, ErrorCode, Index, IndexAddOption, ObjectType,
+    PushOptions, RemoteCallbacks, Repository, RepositoryInitOptions, ResetType, Signature, StatusOptions
+};
+
+use config::GitConfig;
+use error::{Error, Result};
+use super::{FileChange, FileStatus, Git, GitResult, VcsBackend};
+
+/// How many times credentials are requested for a push before giving up.
+const MAX_CREDENTIAL_ATTEMPTS: usize = 3;
+
+/// Git backend using libgit2.
+pub struct LibGit2 {
+    cli: Git,
+    config: GitConfig,
+    working_directory: PathBuf
+}
+
+/// Create an error for a failed libgit2 operation.
+fn get_error(operation: &str, err: git2::Error) -> Error {
+    Error::Git {
+        command: format!("libgit2 {}", operation),
+        code: Some(err.raw_code()),
+        stderr: err.message().to_string()
+    }
+}
+
+impl LibGit2 {
+    /// Get a new libgit2 backend instance. The git binary is only used for
+    /// writing archives, which libgit2 does not support.
+    pub fn new(bin: String, cfg: GitConfig, cwd: PathBuf) -> Self {
+        LibGit2 {
+            cli: Git::new(bin, cfg.clone(), cwd.clone()),
+            config: cfg,
+            working_directory: cwd
+        }
+    }
+
+    /// Open the repository of the working directory.
+    fn open(&self) -> Result<Repository> {
+        Repository::open(&self.working_directory).map_err(|e| get_error("open", e))
+    }
+
+    /// Get the signature used for commits and tags. The identity configured
+    /// in the repository is preferred, like the git binary does.
+    fn get_signature(&self, repo: &Repository) -> Result<Signature<'static>> {
+        repo.signature()
+            .or_else(|_| Signature::now(&self.config.user_name, &self.config.user_email))
+            .map_err(|e| get_error("signature", e))
+    }
+
+    /// Commit the tree of an index on top of `HEAD`, if `HEAD` exists.
+    fn commit_index(&self, repo: &Repository, index: &mut Index, message: &str) -> GitResult {
+        let tree_id = index.write_tree().map_err(|e| get_error("write-tree", e))?;
+        let tree = repo.find_tree(tree_id).map_err(|e| get_error("find-tree", e))?;
+        let signature = self.get_signature(repo)?;
+
+        let parent = match repo.head() {
+            Ok(head) => Some(head.peel_to_commit().map_err(|e| get_error("commit", e))?),
+            Err(ref e) if e.code() == ErrorCode::UnbornBranch || e.code() == ErrorCode::NotFound => None,
+            Err(e) => return Err(get_error("commit", e))
+        };
+
+        let parents: Vec<_> = parent.iter().collect();
+
+        repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents)
+            .map_err(|e| get_error("commit", e))?;
+
+        Ok(true)
+    }
+}
+
+impl VcsBackend for LibGit2 {
+    fn with_working_directory(&self, cwd: PathBuf) -> Box<dyn VcsBackend> {
+        Box::new(LibGit2 {
+            cli: Git::new(self.cli.bin.clone(), self.config.clone(), cwd.clone()),
+            config: self.config.clone(),
+            working_directory: cwd
+        })
+    }
+
+    fn set_repo_git_config(&self) -> GitResult {
+        let repo = self.open()?;
+        let mut config = repo
+            .config()
+            .and_then(|c| c.open_level(ConfigLevel::Local))
+            .map_err(|e| get_error("config", e))?;
+
+        config.set_str("user.name", &self.config.user_name).map_err(|e| get_error("config", e))?;
+        config.set_str("user.email", &self.config.user_email).map_err(|e| get_error("config", e))?;
+
+        Ok(true)
+    }
+
+    fn repository_is_initialized(&self) -> GitResult {
+        let repo = match Repository::open(&self.working_directory) {
+            Ok(repo) => repo,
+            Err(_) => return Ok(false)
+        };
+
+        // the `.git` directory itself opens as a repository without a work tree
+        Ok(match repo.workdir() {
+            Some(workdir) => workdir.canonicalize().ok() == self.working_directory.canonicalize().ok(),
+            None => false
+        })
+    }
+
+    fn initialize_repository(&self) -> GitResult {
+        Repository::init_opts(&self.working_directory, RepositoryInitOptions::new().initial_head("master"))
+            .map_err(|e| get_error("init", e))?;
+
+        self.set_repo_git_config()
+    }
+
+    fn get_status(&self) -> Result<Vec<FileChange>> {
+        let repo = self.open()?;
+        let mut options = StatusOptions::new();
+
+        options.include_untracked(true).recurse_untracked_dirs(true);
+
+        let statuses = repo.statuses(Some(&mut options)).map_err(|e| get_error("status", e))?;
+
+        Ok(statuses
+            .iter()
+            .filter_map(|entry| {
+                let flags = entry.status();
+                let status = if flags.is_ignored() {
+                    return None;
+                } else if flags.is_wt_new() && !flags.is_index_new() {
+                    FileStatus::Untracked
+                } else if flags.is_index_deleted() || flags.is_wt_deleted() {
+                    FileStatus::Deleted
+                } else if flags.is_index_new() {
+                    FileStatus::Added
+                } else {
+                    FileStatus::Modified
+                };
+
+                Some(FileChange {
+                    path: String::from_utf8_lossy(entry.path_bytes()).to_string(),
+                    status
+                })
+            })
+            .collect())
+    }
+
+    fn add_and_commit_changes(&self, message: &str) -> GitResult {
+        if self.config.sign.is_some() {
+            return self.cli.add_and_commit_changes(message);
+        }
+
+        if !self.has_uncommited_changes()? {
+            return Ok(false);
+        }
+
+        let repo = self.open()?;
+        let mut index = repo.index().map_err(|e| get_error("add", e))?;
+
+        index.add_all(["."].iter(), IndexAddOption::DEFAULT, None).map_err(|e| get_error("add", e))?;
+        index.update_all(["."].iter(), None).map_err(|e| get_error("add", e))?;
+        index.write().map_err(|e| get_error("add", e))?;
+
+        self.commit_index(&repo, &mut index, message)
+    }
+
+    fn add_remote(&self, remote_uri: String) -> GitResult {
+        let repo = self.open()?;
+
+        repo.remote("wppr", &remote_uri).map(|_| true).or_else(|e| match e.code() {
+            ErrorCode::Exists => Ok(true),
+            _ => Err(get_error("remote add", e))
+        })
+    }
+
+    fn push_to_remote(&self) -> GitResult {
+        let repo = self.open()?;
+        let mut remote = repo.find_remote("wppr").map_err(|e| get_error("push", e))?;
+        let git_config = repo.config().map_err(|e| get_error("push", e))?;
+        let force = if self.config.force_push { "+" } else { "" };
+
+        let mut refspecs = vec![format!("{}refs/heads/master:refs/heads/master", force)];
+
+        for tag in self.list_tags()? {
+            refspecs.push(format!("{}refs/tags/{}:refs/tags/{}", force, tag, tag));
+        }
+
+        let attempts = Cell::new(0);
+        let mut callbacks = RemoteCallbacks::new();
+
+        callbacks.credentials(|url, username, allowed| {
+            attempts.set(attempts.get() + 1);
+
+            if attempts.get() > MAX_CREDENTIAL_ATTEMPTS {
+                return Err(git2::Error::from_str("no valid credentials found"));
+            }
+
+            if allowed.contains(CredentialType::SSH_KEY) {
+                Cred::ssh_key_from_agent(username.unwrap_or("git"))
+            } else if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+                Cred::credential_helper(&git_config, url, username)
+            } else {
+                Cred::default()
+            }
+        });
+
+        callbacks.push_update_reference(|refname, status| match status {
+            Some(message) => Err(git2::Error::from_str(&format!("{} was rejected: {}", refname, message))),
+            None => Ok(())
+        });
+
+        let mut options = PushOptions::new();
+
+        options.remote_callbacks(callbacks);
+
+        remote.push(&refspecs, Some(&mut options)).map_err(|e| get_error("push", e))?;
+
+        Ok(true)
+    }
+
+    fn add_tag(&self, tag: String, message: &str) -> GitResult {
+        if self.config.sign.is_some() {
+            return self.cli.add_tag(tag, message);
+        }
+
+        let repo = self.open()?;
+        let head = repo.revparse_single("HEAD").map_err(|e| get_error("tag", e))?;
+        let signature = self.get_signature(&repo)?;
+
+        repo.tag(&tag, &head, &signature, message, false).map_err(|e| get_error("tag", e))?;
+
+        Ok(true)
+    }
+
+    fn delete_tag(&self, tag: &str) -> GitResult {
+        let repo = self.open()?;
+
+        repo.tag_delete(tag).map_err(|e| get_error("tag delete", e))?;
+
+        Ok(true)
+    }
+
+    fn tag_exists(&self, tag: &str) -> GitResult {
+        let repo = self.open()?;
+
+        Ok(repo.refname_to_id(&format!("refs/tags/{}", tag)).is_ok())
+    }
+
+    fn list_tags(&self) -> Result<Vec<String>> {
+        let repo = self.open()?;
+        let names = repo.tag_names(None).map_err(|e| get_error("tag", e))?;
+        let mut tags: Vec<String> = names.iter().flatten().map(|t| t.to_string()).collect();
+
+        tags.sort();
+
+        Ok(tags)
+    }
+
+    fn get_commit_hash(&self, rev: &str) -> Result<String> {
+        let repo = self.open()?;
+        let commit = repo
+            .revparse_single(rev)
+            .and_then(|o| o.peel_to_commit())
+            .map_err(|e| get_error("rev-parse", e))?;
+
+        Ok(commit.id().to_string())
+    }
+
+    fn get_commit_time(&self, rev: &str) -> Result<String> {
+        let repo = self.open()?;
+        let commit = repo
+            .revparse_single(rev)
+            .and_then(|o| o.peel_to_commit())
+            .map_err(|e| get_error("log", e))?;
+        let time = commit.time();
+
+        FixedOffset::east_opt(time.offset_minutes() * 60)
+            .and_then(|offset| offset.timestamp_opt(time.seconds(), 0).single())
+            .map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, false))
+            .ok_or_else(|| Error::Git {
+                command: "libgit2 log".to_string(),
+                code: None,
+                stderr: format!("Invalid commit time for `{}`", rev)
+            })
+    }
+
+    fn get_file_at(&self, rev: &str, path: &str) -> Result<Option<String>> {
+        let repo = self.open()?;
+        let tree = repo
+            .revparse_single(rev)
+            .and_then(|o| o.peel_to_tree())
+            .map_err(|e| get_error("show", e))?;
+
+        let entry = match tree.get_path(Path::new(path)) {
+            Ok(entry) => entry,
+            Err(ref e) if e.code() == ErrorCode::NotFound => return Ok(None),
+            Err(e) => return Err(get_error("show", e))
+        };
+
+        let object = entry.to_object(&repo).map_err(|e| get_error("show", e))?;
+
+        Ok(object.as_blob().map(|blob| String::from_utf8_lossy(blob.content()).to_string()))
+    }
+
+    fn archive_zip(&self, rev: &str, prefix: &str, dest: &Path) -> GitResult {
+        self.cli.archive_zip(rev, prefix, dest)
+    }
+
+    fn checkout_tree(&self, rev: &str) -> GitResult {
+        let repo = self.open()?;
+        let tree = repo
+            .revparse_single(rev)
+            .and_then(|o| o.peel_to_tree())
+            .map_err(|e| get_error("checkout", e))?;
+
+        repo.checkout_tree(tree.as_object(), Some(CheckoutBuilder::new().force()))
+            .map_err(|e| get_error("checkout", e))?;
+
+        let mut index = repo.index().map_err(|e| get_error("checkout", e))?;
+
+        index.read_tree(&tree).map_err(|e| get_error("checkout", e))?;
+        index.write().map_err(|e| get_error("checkout", e))?;
+
+        Ok(true)
+    }
+
+    fn has_staged_changes(&self) -> GitResult {
+        let repo = self.open()?;
+        let head_tree = match repo.head() {
+            Ok(head) => Some(head.peel_to_tree().map_err(|e| get_error("diff", e))?),
+            Err(ref e) if e.code() == ErrorCode::UnbornBranch || e.code() == ErrorCode::NotFound => None,
+            Err(e) => return Err(get_error("diff", e))
+        };
+
+        let diff = repo
+            .diff_tree_to_index(head_tree.as_ref(), None, None)
+            .map_err(|e| get_error("diff", e))?;
+
+        Ok(diff.deltas().len() > 0)
+    }
+
+    fn commit_staged(&self, message: &str) -> GitResult {
+        if self.config.sign.is_some() {
+            return self.cli.commit_staged(message);
+        }
+
+        let repo = self.open()?;
+        let mut index = repo.index().map_err(|e| get_error("commit", e))?;
+
+        self.commit_index(&repo, &mut index, message)
+    }
+
+    fn reset_contents(&self) -> GitResult {
+        let repo = self.open()?;
+        let head = repo
+            .head()
+            .and_then(|h| h.peel(ObjectType::Commit))
+            .map_err(|e| get_error("reset", e))?;
+
+        repo.reset(&head, ResetType::Hard, None).map_err(|e| get_error("reset", e))?;
+
+        Ok(true)
+    }
+
+    fn reset_to(&self, rev: &str) -> GitResult {
+        let repo = self.open()?;
+        let commit = repo
+            .revparse_single(rev)
+            .and_then(|o| o.peel(ObjectType::Commit))
+            .map_err(|e| get_error("reset", e))?;
+
+        repo.reset(&commit, ResetType::Hard, None).map_err(|e| get_error("reset", e))?;
+
+        Ok(true)
+    }
+
+    fn verify_tag(&self, tag: &str, allowed_signers: &Path) -> Result<Option<String>> {
+        self.cli.verify_tag(tag, allowed_signers)
+    }
+}
@@ -17,25 +17,53 @@
 // License for the specific language governing permissions and limitations
 // under the License.
 
+extern crate base64;
+extern crate chrono;
 extern crate clap;
+extern crate flate2;
+extern crate git2;
+extern crate libc;
 #[macro_use]
 extern crate prettytable;
 extern crate regex;
+extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_ignored;
+extern crate serde_json;
+extern crate sha2;
+extern crate tar;
+extern crate tiny_http;
 extern crate toml;
-extern crate fs_extra;
+extern crate toml_edit;
 
-use clap::{App, Arg, ArgMatches, SubCommand};
-use std::path::PathBuf;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use std::env;
 
+pub mod artifacts;
+pub mod backup;
+pub mod changelog;
 pub mod commands;
+pub mod composer;
 pub mod config;
-mod git;
+pub mod config_editor;
+pub mod error;
+pub mod git;
+pub mod mirror;
 pub mod pipeline;
+pub mod policy;
+pub mod repository;
+pub mod server;
+pub mod shell;
+pub mod version;
 pub mod wordpress;
 
-use config::{RuntimeConfig, TomlConfig};
+use config::{resolve_config_file, RuntimeConfig, TomlConfig};
+
+pub use error::{Error, Result};
+
+/// Exit code of the `outdated` command when `run` would upgrade plugins.
+pub const EXIT_UPDATES_AVAILABLE: i32 = 2;
 
 /// Get the application clap config.
 fn get_app_init_config() -> ArgMatches<'static> {
@@ -49,10 +77,12 @@ fn get_app_init_config() -> ArgMatches<'static> {
         .arg(
             Arg::with_name("config")
                 .long("configuration")
-                .help("Absolute path to a TOML configuration file to use")
+                .help(
+                    "Path to a TOML configuration file to use, defaults to `$WPPR_CONFIG` or \
+                     the closest `wppr.toml` in the current or a parent directory",
+                )
                 .takes_value(true)
-                .value_name("FILE")
-                .required(true),
+                .value_name("FILE"),
         )
         .arg(
             Arg::with_name("verbosity")
@@ -67,6 +97,216 @@ fn get_app_init_config() -> ArgMatches<'static> {
                 .takes_value(false)
                 .help("Run operations without actually making changes"),
         )
+        .subcommand(
+            SubCommand::with_name("init")
+                .about("Create a configuration file from plugins installed in WordPress")
+                .arg(
+                    Arg::with_name("vendor")
+                        .long("vendor")
+                        .takes_value(true)
+                        .value_name("VENDOR")
+                        .required(true)
+                        .help("Composer vendor used for generated package names"),
+                )
+                .arg(
+                    Arg::with_name("remote")
+                        .long("remote")
+                        .takes_value(true)
+                        .value_name("TEMPLATE")
+                        .required(true)
+                        .help(
+                            "Remote repository URL template, `{vendor}` and `{slug}` are \
+                             replaced with plugin values",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("plugin")
+                        .long("plugin")
+                        .takes_value(true)
+                        .value_name("SLUG")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Only include the given plugin, can be given multiple times"),
+                )
+                .arg(
+                    Arg::with_name("non_wporg")
+                        .long("non-wporg")
+                        .takes_value(false)
+                        .help("Only include plugins which are not available on wordpress.org"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Where to write the configuration, defaults to `./wppr.toml`"),
+                )
+                .arg(
+                    Arg::with_name("wpcli")
+                        .long("wpcli")
+                        .takes_value(true)
+                        .value_name("BIN")
+                        .help("WP-CLI executable to use, defaults to `wp`"),
+                )
+                .arg(
+                    Arg::with_name("force")
+                        .long("force")
+                        .takes_value(false)
+                        .help("Overwrite an existing configuration file"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("add")
+                .about("Add an installed plugin to the configuration file")
+                .arg(
+                    Arg::with_name("slug")
+                        .required(true)
+                        .value_name("SLUG")
+                        .help("Slug of the plugin to add"),
+                )
+                .arg(
+                    Arg::with_name("vendor")
+                        .long("vendor")
+                        .takes_value(true)
+                        .value_name("VENDOR")
+                        .required(true)
+                        .help("Composer vendor used for the generated package name"),
+                )
+                .arg(
+                    Arg::with_name("remote")
+                        .long("remote")
+                        .takes_value(true)
+                        .value_name("TEMPLATE")
+                        .required(true)
+                        .help(
+                            "Remote repository URL template, `{vendor}` and `{slug}` are \
+                             replaced with plugin values",
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("remove")
+                .about("Remove a plugin from the configuration file")
+                .arg(
+                    Arg::with_name("slug")
+                        .required(true)
+                        .value_name("SLUG")
+                        .help("Slug of the plugin to remove"),
+                )
+                .arg(
+                    Arg::with_name("purge")
+                        .long("purge")
+                        .takes_value(false)
+                        .help("Also delete the git repository and composer.json created for the plugin"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export-repository")
+                .about("Export managed plugins into a static Composer repository")
+                .arg(
+                    Arg::with_name("dir")
+                        .required(true)
+                        .value_name("DIR")
+                        .help("Directory to write the repository `packages.json` into"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("serve")
+                .about("Serve the Composer repository of managed plugins over HTTP")
+                .arg(
+                    Arg::with_name("listen")
+                        .long("listen")
+                        .takes_value(true)
+                        .value_name("ADDR")
+                        .help("Address to listen on, defaults to `127.0.0.1:8080`"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("rollback")
+                .about("Roll a plugin back to a previously tagged version")
+                .arg(
+                    Arg::with_name("slug")
+                        .required(true)
+                        .value_name("PLUGIN")
+                        .help("Slug of the plugin to roll back"),
+                )
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .takes_value(true)
+                        .value_name("VERSION")
+                        .help("Version to roll back to, defaults to the version before the installed one"),
+                )
+                .arg(
+                    Arg::with_name("hold")
+                        .long("hold")
+                        .takes_value(false)
+                        .help("Hold the plugin at the version so that `run` does not upgrade it"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("backup")
+                .about("List, restore, and prune plugin backups")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .about("List plugin backups")
+                        .arg(
+                            Arg::with_name("slug")
+                                .value_name("PLUGIN")
+                                .help("Only list backups of the given plugin"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("restore")
+                        .about("Replace a plugin directory with one of its backups")
+                        .arg(
+                            Arg::with_name("slug")
+                                .required(true)
+                                .value_name("PLUGIN")
+                                .help("Slug of the plugin to restore"),
+                        )
+                        .arg(
+                            Arg::with_name("backup_id")
+                                .required(true)
+                                .value_name("BACKUP_ID")
+                                .help("Backup to restore, as shown by `backup list`"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("prune")
+                        .about("Remove old plugin backups")
+                        .arg(
+                            Arg::with_name("keep")
+                                .long("keep")
+                                .takes_value(true)
+                                .value_name("COUNT")
+                                .validator(|k| {
+                                    match k.parse::<usize>() {
+                                        Ok(n) if n > 0 => Ok(()),
+                                        _ => Err("must be a positive number".to_string())
+                                    }
+                                })
+                                .help("Backups to keep per plugin, defaults to `backups.keep`"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("outdated")
+                .about("List available plugin updates without applying them")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .value_name("FORMAT")
+                        .possible_values(&["table", "json"])
+                        .help("Output format, defaults to `table`"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("verify")
+                .about("Verify that every tag of the plugin repositories is signed by a trusted key"),
+        )
         .subcommand(
             SubCommand::with_name("list")
                 .about("List plugins being managed by chosen configuration"),
@@ -80,41 +320,135 @@ fn get_app_init_config() -> ArgMatches<'static> {
 
 /// Get application runtime configuration which has been read from a provided
 /// TOML configuration file.
-fn get_app_run_config(init_config: &ArgMatches) -> Result<RuntimeConfig, String> {
+fn get_app_run_config(init_config: &ArgMatches) -> Result<RuntimeConfig> {
     let verbose: bool = init_config.is_present("verbosity");
     let dry_run: bool = init_config.is_present("dryrun");
-    let config_file: &str = init_config.value_of("config").unwrap();
-    let cfg_path = PathBuf::from(config_file);
+    let given_file: Option<String> = init_config
+        .value_of("config")
+        .map(|f| f.to_string())
+        .or_else(|| env::var("WPPR_CONFIG").ok().filter(|f| !f.is_empty()));
 
-    let mut toml_configuration: TomlConfig = match TomlConfig::load_from_file(cfg_path) {
-        Ok(c) => c,
-        Err(e) => {
-            return Err(e.to_string());
-        }
-    };
+    let cfg_path = resolve_config_file(given_file, &env::current_dir()?)?;
+
+    let mut toml_configuration: TomlConfig = TomlConfig::load_from_file(cfg_path)?;
+
+    for key in &toml_configuration.unknown_keys {
+        eprintln!("Warning: ignoring unknown configuration key `{}`", key);
+    }
 
     toml_configuration.set_verbosity(verbose);
     toml_configuration.set_dry_run(dry_run);
 
-    match RuntimeConfig::from_toml_config(toml_configuration) {
-        Ok(cfg) => Ok(cfg),
-        Err(estring) => Err(format!("Invalid configuration: {}", estring)),
+    RuntimeConfig::from_toml_config(toml_configuration)
+}
+
+/// Run the `init` command of this tool. Does not require an existing
+/// configuration.
+fn run_init_command(init_config: &ArgMatches) -> Result<bool> {
+    let args = init_config.subcommand_matches("init").unwrap();
+    let cwd = env::current_dir()?;
+
+    let options = commands::InitOptions {
+        wpcli: args.value_of("wpcli").unwrap_or("wp").to_string(),
+        vendor: args.value_of("vendor").unwrap().to_string(),
+        remote_template: args.value_of("remote").unwrap().to_string(),
+        plugins: args
+            .values_of("plugin")
+            .map(|v| v.map(|p| p.to_string()).collect())
+            .unwrap_or_default(),
+        non_wporg_only: args.is_present("non_wporg"),
+        output: cwd.join(args.value_of("output").unwrap_or(config::CONFIG_FILE_NAME)),
+        force: args.is_present("force"),
+        dry_run: init_config.is_present("dryrun"),
+    };
+
+    commands::init(options)
+}
+
+/// Run the `add` command of this tool.
+fn run_add_command(config: RuntimeConfig, args: &ArgMatches) -> Result<bool> {
+    commands::add(
+        config,
+        args.value_of("slug").unwrap(),
+        args.value_of("vendor").unwrap(),
+        args.value_of("remote").unwrap(),
+    )
+}
+
+/// Run the `remove` command of this tool.
+fn run_remove_command(config: RuntimeConfig, args: &ArgMatches) -> Result<bool> {
+    commands::remove(config, args.value_of("slug").unwrap(), args.is_present("purge"))
+}
+
+/// Run the `export-repository` command of this tool.
+fn run_export_repository_command(config: RuntimeConfig, args: &ArgMatches) -> Result<bool> {
+    let dir = env::current_dir()?.join(args.value_of("dir").unwrap());
+
+    commands::export_repository(config, &dir)
+}
+
+/// Run the `serve` command of this tool.
+fn run_serve_command(config: RuntimeConfig, args: &ArgMatches) -> Result<bool> {
+    commands::serve(config, args.value_of("listen").unwrap_or("127.0.0.1:8080"))
+}
+
+/// Run the `rollback` command of this tool.
+fn run_rollback_command(config: RuntimeConfig, args: &ArgMatches) -> Result<bool> {
+    commands::rollback(
+        config,
+        args.value_of("slug").unwrap(),
+        args.value_of("to"),
+        args.is_present("hold"),
+    )
+}
+
+/// Run the `backup` command of this tool.
+fn run_backup_command(config: RuntimeConfig, args: &ArgMatches) -> Result<bool> {
+    match args.subcommand() {
+        ("list", Some(a)) => commands::backup_list(config, a.value_of("slug")),
+        ("restore", Some(a)) => commands::backup_restore(
+            config,
+            a.value_of("slug").unwrap(),
+            a.value_of("backup_id").unwrap(),
+        ),
+        ("prune", Some(a)) => commands::backup_prune(config, a.value_of("keep").and_then(|k| k.parse().ok())),
+        _ => Ok(false)
+    }
+}
+
+/// Run the `outdated` command of this tool. Exits with
+/// `EXIT_UPDATES_AVAILABLE` if `run` would upgrade any managed plugin.
+fn run_outdated_command(config: RuntimeConfig, args: &ArgMatches) -> i32 {
+    match commands::outdated(config, args.value_of("format") == Some("json")) {
+        Ok(0) => 0,
+        Ok(_) => EXIT_UPDATES_AVAILABLE,
+        Err(e) => get_exit_code(Err(e))
     }
 }
 
 /// Run the `list` command of this tool.
-fn run_list_command(config: RuntimeConfig) -> Result<bool, String> {
+fn run_list_command(config: RuntimeConfig) -> Result<bool> {
     commands::list(config)
 }
 
+/// Run the `verify` command of this tool.
+fn run_verify_command(config: RuntimeConfig) -> Result<bool> {
+    commands::verify(config)
+}
+
 /// Run the `run` command of this tool.
-fn run_run_command(config: RuntimeConfig) -> Result<bool, String> {
+fn run_run_command(config: RuntimeConfig) -> Result<bool> {
     commands::run(config)
 }
 
 /// Run the application. Returns an interger for exit coding.
 pub fn run() -> i32 {
     let app_config: ArgMatches = get_app_init_config();
+
+    if app_config.subcommand_name() == Some("init") {
+        return get_exit_code(run_init_command(&app_config));
+    }
+
     let runtime_configuration = get_app_run_config(&app_config);
 
     let configuration: RuntimeConfig = match runtime_configuration {
@@ -129,15 +463,28 @@ pub fn run() -> i32 {
         println!("Configuration: {:?}", configuration);
     };
 
-    let command_result = match app_config.subcommand_name() {
-        Some("list") => run_list_command(configuration),
-        Some("run") => run_run_command(configuration),
+    let command_result = match app_config.subcommand() {
+        ("add", Some(args)) => run_add_command(configuration, args),
+        ("remove", Some(args)) => run_remove_command(configuration, args),
+        ("export-repository", Some(args)) => run_export_repository_command(configuration, args),
+        ("serve", Some(args)) => run_serve_command(configuration, args),
+        ("rollback", Some(args)) => run_rollback_command(configuration, args),
+        ("backup", Some(args)) => run_backup_command(configuration, args),
+        ("outdated", Some(args)) => return run_outdated_command(configuration, args),
+        ("verify", _) => run_verify_command(configuration),
+        ("list", _) => run_list_command(configuration),
+        ("run", _) => run_run_command(configuration),
         _ => {
             eprintln!("Invalid command given");
             return 1;
         }
     };
 
+    get_exit_code(command_result)
+}
+
+/// Get an exit code for a command result, printing possible errors.
+fn get_exit_code(command_result: Result<bool>) -> i32 {
     match command_result {
         Ok(_) => 0,
         Err(e) => {
@@ -0,0 +1,79 @@
+//! # mirror
+//!
+//! Plugin mirror repositories which are kept outside the WordPress
+//! installation. The plugin directory is synced into the mirror checkout
+//! after upgrades, so WordPress never touches git state and the plugin
+//! directory is never written to by wppr.
+
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    fs::{copy, create_dir_all, read_dir, read_link, remove_dir_all, remove_file, symlink_metadata},
+    os::unix::fs::symlink,
+    path::Path
+};
+
+use error::Result;
+
+/// Files in the root of a mirror which are managed by wppr and never synced.
+pub const MIRROR_FILES: [&str; 2] = [".git", "composer.json"];
+
+/// Make the contents of `dest` match `src`. Files and directories missing
+/// from `src` are removed from `dest`. Names in `excluded` are neither copied
+/// nor removed in the root of the directories.
+pub fn sync_directory(src: &Path, dest: &Path, excluded: &[&str]) -> Result<()> {
+    create_dir_all(dest)?;
+
+    let excluded: HashSet<OsString> = excluded.iter().map(OsString::from).collect();
+    let mut synced: HashSet<OsString> = HashSet::new();
+
+    for entry in read_dir(src)? {
+        let entry = entry?;
+        let name = entry.file_name();
+
+        if excluded.contains(&name) {
+            continue;
+        }
+
+        let src_path = entry.path();
+        let dest_path = dest.join(&name);
+        let metadata = symlink_metadata(&src_path)?;
+
+        // an entry of another type is replaced instead of synced into
+        if let Ok(dest_metadata) = symlink_metadata(&dest_path) {
+            if dest_metadata.file_type() != metadata.file_type() || metadata.file_type().is_symlink() {
+                remove_path(&dest_path)?;
+            }
+        }
+
+        if metadata.file_type().is_symlink() {
+            symlink(read_link(&src_path)?, &dest_path)?;
+        } else if metadata.is_dir() {
+            sync_directory(&src_path, &dest_path, &[])?;
+        } else {
+            copy(&src_path, &dest_path)?;
+        }
+
+        synced.insert(name);
+    }
+
+    for entry in read_dir(dest)? {
+        let name = entry?.file_name();
+
+        if !excluded.contains(&name) && !synced.contains(&name) {
+            remove_path(&dest.join(&name))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove a file, symlink, or a whole directory.
+fn remove_path(path: &Path) -> Result<()> {
+    match symlink_metadata(path)?.is_dir() {
+        true => remove_dir_all(path)?,
+        false => remove_file(path)?
+    };
+
+    Ok(())
+}
@@ -3,24 +3,51 @@
 //! Defines an upgrade pipeline that can be used to upgrade and gitify single
 //! WordPress plugins.
 
-use fs_extra;
+use chrono::Utc;
 
 use std::{
-    fs::write,
-    path::PathBuf
+    fs::{read_to_string, write},
+    path::{Path, PathBuf}
 };
 
+use artifacts::build_dist_archive;
+use backup::{BackupEntry, BackupStore};
+use changelog::find_version_changelog;
+use composer::{get_plugin_composerjson, to_json_string};
 use config::RuntimeConfig;
-use git::Git;
-use wordpress::{Plugin, WpCli, get_plugin_version};
+use error::{Error, Result};
+use git::{get_backend, get_change_summary, render_commit_message, VcsBackend, AUTOMATED_COMMIT_MESSAGE};
+use mirror::sync_directory;
+use shell::Shell;
+use version::Version;
+use wordpress::{Plugin, PluginHeader, WpCli, get_plugin_version};
+
+/// Outcome of a pipeline run which finished without errors.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UpgradeOutcome {
+    /// Plugin was upgraded, tagged, and pushed.
+    Upgraded { from: String, to: String },
+    /// Plugin had no upgrades available.
+    UpToDate,
+    /// Pipeline was run without making changes.
+    DryRun,
+    /// An update is available but the upgrade policy of the plugin does not
+    /// allow it.
+    BlockedByPolicy { available: String, reason: String },
+}
 
 /// Data for an upgrade pipeline.
 pub struct Pipeline {
     plugin: Plugin,
-    has_backup: bool,
-    backup_dir: PathBuf,
-    git_cli: Git,
+    backup: Option<BackupEntry>,
+    backups: BackupStore,
+    backups_keep: usize,
+    git: Box<dyn VcsBackend>,
+    mirror_dir: Option<PathBuf>,
+    commit_message: String,
     wp_cli: WpCli,
+    shell: Shell,
+    artifacts_dir: Option<PathBuf>,
     dry_run: bool,
     verbose: bool
 }
@@ -28,28 +55,37 @@ pub struct Pipeline {
 /// Pipeline implementation.
 impl Pipeline {
     /// Create a new pipeline instance.
-    pub fn new(config: &RuntimeConfig, plugin: &Plugin, backup_dir: &PathBuf) -> Result<Pipeline, String> {
+    pub fn new(config: &RuntimeConfig, plugin: &Plugin, backup_dir: &PathBuf) -> Result<Pipeline> {
         if config.dry_run {
             println!("Creating dry run pipeline for plugin `{}`", plugin.get_nicename());
         }
 
-        let nicename = plugin.get_cli_name()?;
-
-        let mut backup_subdir: PathBuf = backup_dir.clone();
-        backup_subdir.push(nicename);
+        let plugin_dir = plugin.get_plugin_dir();
 
-        let mut plugin_dir = plugin.index_path.clone();
-        plugin_dir.pop();
+        let mirror_dir = config.get_mirror_dir(plugin)?;
+        let repository_dir = mirror_dir.clone().unwrap_or_else(|| plugin_dir.clone());
 
-        let git = Git::new(config.binaries.git.clone(), config.git.clone(), plugin_dir.clone());
+        let git = get_backend(config.binaries.git.clone(), config.git.clone(), repository_dir);
         let wp = WpCli::new(config.binaries.wpcli.clone(), plugin_dir.clone());
 
+        let mut shell = Shell::new(config.binaries.get_shell(), plugin_dir.clone(), plugin.cmds_timeout);
+
+        shell.set_env("WPPR_PLUGIN_NICENAME", &plugin.get_nicename());
+        shell.set_env("WPPR_PLUGIN_VERSION", &plugin.installed_version.clone().unwrap_or_default());
+        shell.set_env("WPPR_PACKAGE_NAME", &plugin.package_name);
+        shell.set_env("WPPR_PLUGIN_DIR", &plugin_dir.to_string_lossy());
+
         Ok(Pipeline {
             plugin: (*plugin).clone(),
-            has_backup: false,
-            backup_dir: backup_subdir,
-            git_cli: git,
+            backup: None,
+            backups: BackupStore::new(backup_dir.clone()),
+            backups_keep: config.backups.get_keep(),
+            git,
+            mirror_dir,
+            commit_message: config.git.get_commit_message(),
             wp_cli: wp,
+            shell,
+            artifacts_dir: config.get_artifacts_dir(),
             dry_run: config.dry_run,
             verbose: config.verbose
         })
@@ -64,15 +100,28 @@ impl Pipeline {
 
     /// Run the pipeline, first by maybe initing the plugin and then doing
     /// upgrades.
-    pub fn run(&mut self) -> Result<bool, String> {
+    pub fn run(&mut self) -> Result<UpgradeOutcome> {
         self.progress_log("Starting upgrade run");
 
         self.maybe_initialize_plugin()?;
+
+        if let Some(blocked) = self.check_available_update()? {
+            self.progress_log("Available update is blocked by the upgrade policy");
+
+            return Ok(blocked);
+        }
+
         self.create_backup()?;
 
         if self.plugin.pre_cmds.is_empty() == false {
             self.progress_log("Running plugin pre-commands before upgrade");
-            self.run_pre_cmds()?;
+
+            if let Err(s) = self.run_pre_cmds() {
+                self.restore_backup()?;
+                self.reset_repository()?;
+
+                return Err(s);
+            }
         }
 
         let updated = self.update_plugin();
@@ -83,224 +132,474 @@ impl Pipeline {
                     true => (), // update was made and succeeded
                     false => {
                         self.progress_log("Plugin already up to date, proceeding");
-                        return Ok(true)
+                        return Ok(UpgradeOutcome::UpToDate)
                     }
                 }
             },
             Err(s) => {
                 self.restore_backup()?;
-                self.git_cli.reset_contents()?;
+                self.reset_repository()?;
 
                 return Err(s);
             }
         }
 
-        self.restore_backup()?; // as the upgrade removed our git and composerjson we restore them
+        self.restore_git_history()?; // as the upgrade removed our git and composerjson we restore them
 
-        if self.dry_run == false && self.git_cli.has_uncommited_changes()? == false {
+        if self.dry_run == false && self.git.has_uncommited_changes()? == false {
             // no changes done during update, we're done here
-            return Ok(true);
+            return Ok(UpgradeOutcome::UpToDate);
         }
 
-        let current_version = self.plugin.installed_version.clone().unwrap();
-        let new_version = get_plugin_version(&self.plugin)?;
+        let (current_version, new_version) = match self.get_upgrade_versions() {
+            Ok(v) => v,
+            Err(s) => {
+                self.restore_backup()?;
+                self.reset_repository()?;
+
+                return Err(s);
+            }
+        };
+
+        if !self.dry_run && new_version < current_version {
+            self.restore_backup()?;
+            self.reset_repository()?;
+
+            return Err(Error::Version(format!(
+                "Upgrading `{}` would downgrade it from {} to {}",
+                self.plugin.get_nicename(),
+                current_version,
+                new_version
+            )));
+        }
 
         if self.dry_run == false && current_version == new_version {
             self.restore_backup()?;
-            self.git_cli.reset_contents()?;
+            self.reset_repository()?;
 
             // no upgrade done, break out
-            return Ok(true);
+            return Ok(UpgradeOutcome::UpToDate);
+        }
+
+        // WP-CLI does not know the update version of all plugins beforehand
+        if !self.dry_run {
+            if let Some(reason) = self.plugin.policy.check(&current_version, &new_version)? {
+                self.restore_backup()?;
+                self.reset_repository()?;
+
+                return Ok(UpgradeOutcome::BlockedByPolicy {
+                    available: new_version.to_string(),
+                    reason
+                });
+            }
         }
 
-        let result: Result<bool, String>;
+        let tag = new_version.to_tag();
+        let mut created_tag = None;
 
         if self.dry_run == false {
-            self.git_cli.add_and_commit_changes()?;
-            self.git_cli.add_tag(new_version)?;
+            match self.commit_upgrade(&current_version, &new_version, &tag) {
+                Ok(t) => created_tag = t,
+                Err(s) => {
+                    self.undo_upgrade(None)?;
 
-            result = self.git_cli.push_to_remote();
-        } else {
-            result = Ok(true);
+                    return Err(s);
+                }
+            };
         }
 
+        let current_version = current_version.to_string();
+        let new_version = new_version.to_string();
+
+        let result: Result<bool> = match self.run_post_cmds(&current_version, &new_version) {
+            Ok(_) if self.dry_run => Ok(true),
+            Ok(_) => self.build_dist_archive(&tag).and_then(|_| self.git.push_to_remote()),
+            Err(s) => Err(s)
+        };
+
         match result {
             Ok(_) => {
                 self.progress_log("Upgrade run finished");
 
-                Ok(true)
+                if self.dry_run {
+                    return Ok(UpgradeOutcome::DryRun);
+                }
+
+                Ok(UpgradeOutcome::Upgraded {
+                    from: current_version,
+                    to: new_version
+                })
             },
             Err(s) => {
-                self.restore_backup()?;
-                self.git_cli.reset_contents()?;
+                self.undo_upgrade(created_tag.as_deref())?;
 
                 return Err(s);
             }
         }
     }
 
+    /// Commit the upgrade and tag the new version. Returns the tag if it was
+    /// created during this run.
+    fn commit_upgrade(&self, current_version: &Version, new_version: &Version, tag: &str) -> Result<Option<String>> {
+        let summary = get_change_summary(&self.git.get_status()?);
+
+        for line in summary.lines().filter(|l| !l.is_empty()) {
+            self.progress_log(line);
+        }
+
+        let message = self.get_commit_message(current_version, new_version)?;
+
+        self.git.add_and_commit_changes(&format!("{}\n\n{}", message, summary))?;
+
+        // the tag exists already if the plugin was rolled back earlier
+        if self.git.tag_exists(tag)? {
+            self.progress_log(&format!("Tag `{}` already exists, not tagging again", tag));
+
+            return Ok(None);
+        }
+
+        self.git.add_tag(tag.to_string(), &self.get_tag_message(new_version)?)?;
+
+        Ok(Some(tag.to_string()))
+    }
+
+    /// Undo an upgrade which failed after its changes were synced into the
+    /// repository. A tag left behind would be reused by the next upgrade to
+    /// the same version, so the tag created during this run is deleted too.
+    fn undo_upgrade(&mut self, created_tag: Option<&str>) -> Result<()> {
+        if let Some(tag) = created_tag {
+            self.progress_log(&format!("Deleting tag `{}` of the failed upgrade", tag));
+            self.git.delete_tag(tag)?;
+        }
+
+        self.restore_backup()?;
+        self.reset_repository()
+    }
+
+    /// Check the update WP-CLI offers against the upgrade policy of the
+    /// plugin. Returns the outcome of the run if the update is blocked.
+    fn check_available_update(&self) -> Result<Option<UpgradeOutcome>> {
+        if !self.plugin.policy.is_restricted() {
+            return Ok(None);
+        }
+
+        let available = match self.wp_cli.get_update_version(&self.plugin)? {
+            Some(v) => v,
+            None => return Ok(None)
+        };
+
+        let (current_version, new_version) = match (
+            self.plugin.installed_version.as_ref().map(|v| Version::parse(v)),
+            Version::parse(&available)
+        ) {
+            (Some(Ok(c)), Ok(n)) => (c, n),
+            _ => return Ok(None)
+        };
+
+        match self.plugin.policy.check(&current_version, &new_version)? {
+            Some(reason) => Ok(Some(UpgradeOutcome::BlockedByPolicy { available, reason })),
+            None => Ok(None)
+        }
+    }
+
+    /// Get the versions of the plugin before and after the upgrade.
+    fn get_upgrade_versions(&self) -> Result<(Version, Version)> {
+        let current_version = match self.plugin.installed_version {
+            Some(ref v) => Version::parse(v)?,
+            None => {
+                return Err(Error::Version(format!(
+                    "Installed version of `{}` is unknown",
+                    self.plugin.get_nicename()
+                )));
+            }
+        };
+
+        let new_version = Version::parse(&get_plugin_version(&self.plugin)?)?;
+
+        Ok((current_version, new_version))
+    }
+
+    /// Get the message of the upgrade commit from the configured template.
+    fn get_commit_message(&self, current_version: &Version, new_version: &Version) -> Result<String> {
+        Ok(render_commit_message(
+            &self.commit_message,
+            &self.plugin.get_cli_name()?,
+            &current_version.to_string(),
+            &new_version.to_string(),
+            &Utc::now().format("%Y-%m-%d").to_string()
+        ))
+    }
+
+    /// Get the message of the annotated tag of a new version, which contains
+    /// the changelog of the version if the plugin ships one.
+    fn get_tag_message(&self, new_version: &Version) -> Result<String> {
+        let title = format!("{} {}", self.plugin.get_cli_name()?, new_version);
+
+        match find_version_changelog(&self.plugin.get_plugin_dir(), &new_version.to_string()) {
+            Some(changelog) => Ok(format!("{}\n\n{}", title, changelog)),
+            None => {
+                self.progress_log(&format!("No changelog found for version {}", new_version));
+
+                Ok(title)
+            }
+        }
+    }
+
+    /// Build a dist archive of the tagged release if an artifacts directory
+    /// has been configured.
+    fn build_dist_archive(&self, tag: &str) -> Result<()> {
+        let artifacts_dir = match self.artifacts_dir {
+            Some(ref d) => d,
+            None => return Ok(())
+        };
+
+        self.progress_log(&format!("Building dist archive for `{}`", tag));
+
+        let archive = build_dist_archive(&*self.git, &self.plugin, tag, artifacts_dir)?;
+
+        if self.verbose {
+            self.progress_log(&format!("Dist archive written to `{}`", archive.display()));
+        }
+
+        Ok(())
+    }
+
     /// Run plugin-defined pre-commands. They are just shell commands defined in
     /// the WPPR config.
-    fn run_pre_cmds(&self) -> Result<(), String> {
-        unimplemented!()
+    fn run_pre_cmds(&self) -> Result<()> {
+        for cmd in &self.plugin.pre_cmds {
+            self.run_shell_cmd(cmd)?;
+        }
+
+        Ok(())
     }
 
-    fn maybe_initialize_plugin(&self) -> Result<(), String> {
+    /// Run plugin-defined post-commands after the upgrade has been committed
+    /// and tagged but before it is pushed. Old and new plugin versions are
+    /// exposed to the commands.
+    fn run_post_cmds(&mut self, old_version: &str, new_version: &str) -> Result<()> {
+        if self.plugin.post_cmds.is_empty() {
+            return Ok(());
+        }
+
+        self.progress_log("Running plugin post-commands after upgrade");
+
+        self.shell.set_env("WPPR_OLD_VERSION", old_version);
+        self.shell.set_env("WPPR_NEW_VERSION", new_version);
+
+        for cmd in &self.plugin.post_cmds {
+            self.run_shell_cmd(cmd)?;
+        }
+
+        Ok(())
+    }
+
+    /// Run a single shell command inside the plugin directory, logging its
+    /// output. Errors if the command fails or times out.
+    fn run_shell_cmd(&self, cmd: &str) -> Result<()> {
+        self.progress_log(&format!("Running command `{}`", cmd));
+
+        if self.dry_run {
+            return Ok(());
+        }
+
+        let output = self.shell.run(cmd)?;
+
+        for line in output.stdout.lines() {
+            self.progress_log(&format!("stdout: {}", line));
+        }
+
+        for line in output.stderr.lines() {
+            self.progress_log(&format!("stderr: {}", line));
+        }
+
+        match output.error() {
+            None => Ok(()),
+            Some(e) => Err(e)
+        }
+    }
+
+    fn maybe_initialize_plugin(&self) -> Result<()> {
+        self.sync_mirror()?;
         self.initialize_git_repo_for_plugin()?;
-        self.create_composerjson_for_plugin()?;
+        self.write_composerjson_for_plugin()?;
 
         return Ok(());
     }
 
-    fn initialize_git_repo_for_plugin(&self) -> Result<(), String> {
+    fn initialize_git_repo_for_plugin(&self) -> Result<()> {
         self.progress_log("Initializing git repo if one does not exist");
 
         if self.dry_run {
             return Ok(());
         }
 
-        let git_inited = match self.git_cli.repository_is_initialized()? {
+        let git_inited = match self.git.repository_is_initialized()? {
             true => true,
-            false => self.git_cli.initialize_repository()? && self.git_cli.set_repo_git_config()?
+            false => self.git.initialize_repository()? && self.git.set_repo_git_config()?
         };
 
         if git_inited == false {
-            return Err(format!(
+            return Err(Error::Plugin(format!(
                 "Failed to initialize git repository for plugin `{}`",
                 self.plugin.get_nicename()
-            ));
+            )));
         }
 
-        self.git_cli.add_remote(self.plugin.remote_repository.clone())?;
-        self.git_cli.add_and_commit_changes()?; // add the initial contents
+        self.git.add_remote(self.plugin.remote_repository.clone())?;
+        self.git.add_and_commit_changes(AUTOMATED_COMMIT_MESSAGE)?; // add the initial contents
 
         return Ok(());
     }
 
-    fn create_composerjson_for_plugin(&self) -> Result<(), String> {
-        self.progress_log("Creating composer.json if it does not exist");
+    /// Write a composer.json generated from the current plugin header, using
+    /// the composer strategy and overrides of the plugin.
+    fn write_composerjson_for_plugin(&self) -> Result<()> {
+        self.progress_log("Generating composer.json from plugin header");
 
         if self.dry_run {
             return Ok(());
         }
 
-        let composerjson_path = self.plugin.get_composerjson_path();
+        let composerjson_path = match self.mirror_dir {
+            Some(ref d) => d.join("composer.json"),
+            None => self.plugin.get_composerjson_path()
+        };
 
-        if composerjson_path.exists() {
-            return Ok(());
-        }
+        let existing: Option<String> = match composerjson_path.exists() {
+            true => Some(read_to_string(&composerjson_path)?),
+            false => None
+        };
 
-        let initial_contents = format!("{{\
-    \"name\": \"{}\"\
-    \"type\": \"wordpress-plugin\"\
-}}", self.plugin.package_name);
+        let header = match PluginHeader::from_file(&self.plugin.index_path) {
+            Ok(h) => h,
+            Err(_) => self.plugin.header.clone().unwrap_or_default()
+        };
 
-        let result = write(composerjson_path, initial_contents);
+        match get_plugin_composerjson(&self.plugin, &header, existing.as_deref())? {
+            Some(composerjson) => write(composerjson_path, to_json_string(&composerjson))?,
+            None => self.progress_log("Keeping existing composer.json")
+        };
 
-        match result {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                let errstr = format!("{}", e);
-                Err(errstr)
-            }
-        }
+        Ok(())
     }
 
-    fn create_backup(&mut self) -> Result<(), String> {
-        self.progress_log("Creating history data and config backup");
+    /// Create a backup of the whole plugin directory and prune old backups
+    /// of the plugin.
+    fn create_backup(&mut self) -> Result<()> {
+        self.progress_log("Creating plugin directory backup");
 
         if self.dry_run {
             return Ok(());
         }
 
-        let git_dir = self.plugin.get_git_dir()?;
-        let mut dest = self.backup_dir.clone();
-        dest.push(".git");
-
-        let mut copts = fs_extra::dir::CopyOptions::new();
-        copts.copy_inside = true;
-        copts.overwrite = true;
+        let commit = self.git.get_commit_hash("HEAD").ok();
+        let backup = self.backups.create(&self.plugin, commit)?;
 
         if self.verbose {
-            self.progress_log(&format!("Working with backup directory `{:?}`", self.backup_dir));
-            self.progress_log(&format!("Copying files from `{:?}` to `{:?}`", git_dir, dest));
+            self.progress_log(&format!(
+                "Backup `{}` written to `{}`",
+                backup.id,
+                self.backups.get_archive_path(&backup).display()
+            ));
         }
 
-        let _ = fs_extra::dir::create(&dest, true);
-        let backup_result = fs_extra::dir::copy(&git_dir, &dest, &copts);
+        let pruned = self.backups.prune(&backup.plugin, self.backups_keep)?;
 
-        match backup_result {
-            Ok(_) => {
-                let mut backedup_git = self.backup_dir.clone();
-                backedup_git.push(".git");
-                let gitexist = backedup_git.exists() && backedup_git.is_dir();
-
-                if gitexist == false {
-                    Err(format!(
-                        "Failed to backup plugin .git directory for `{}`", self.plugin.get_nicename()
-                    ))
-                } else {
-                    self.has_backup = true;
-
-                    Ok(())
-                }
-            },
-            Err(e) => Err(format!(
-                "Creating backup failed for plugin `{}`: `{:?}`",
-                self.plugin.get_nicename(),
-                e
-            ))
+        for removed in pruned {
+            if self.verbose {
+                self.progress_log(&format!("Removed old backup `{}`", removed.id));
+            }
         }
+
+        self.backup = Some(backup);
+
+        Ok(())
     }
 
-    fn restore_backup(&mut self) -> Result<(), String> {
-        self.progress_log("Restoring history data and config");
+    /// Get the backup created during this run.
+    fn get_backup(&self) -> Result<BackupEntry> {
+        match self.backup {
+            Some(ref b) => Ok(b.clone()),
+            None => Err(Error::Backup(format!(
+                "Cannot restore backup for `{}`, no backup has been created yet",
+                self.plugin.get_nicename()
+            )))
+        }
+    }
+
+    /// Restore the whole plugin directory from the backup, undoing all
+    /// changes made during this run.
+    fn restore_backup(&mut self) -> Result<()> {
+        self.progress_log("Restoring plugin directory from backup");
 
         if self.dry_run {
             return Ok(());
         }
 
-        if self.has_backup == false {
-            return Err(format!(
-                "Cannot restore backup for `{}`, no backup has been created yet",
-                self.plugin.get_nicename()
-            ));
-        }
+        let backup = self.get_backup()?;
 
-        self.create_composerjson_for_plugin()?;
+        // mirrors are reset separately, backups only contain plugin files
+        match self.mirror_dir {
+            Some(_) => self.backups.restore(&backup, &self.plugin.get_plugin_dir()),
+            None => restore_plugin_backup(&*self.git, &self.backups, &backup, &self.plugin.get_plugin_dir())
+        }
+    }
 
-        let mut backedup_gitdir = self.backup_dir.clone();
-        backedup_gitdir.push(".git");
+    /// Reset the plugin repository to the commit it was at before this run.
+    /// Backups only hold plugin files, so this is what drops the upgrade
+    /// commit from a mirror.
+    fn reset_repository(&self) -> Result<()> {
+        match self.backup.as_ref().and_then(|b| b.commit.as_ref()) {
+            Some(commit) => self.git.reset_to(commit)?,
+            None => self.git.reset_contents()?
+        };
 
-        // remove the existing git dir to make a clean copy possible
-        let mut plugin_gitdir = self.plugin.get_git_dir_path();
-        let _ = fs_extra::dir::create(&plugin_gitdir, true);
+        Ok(())
+    }
 
-        let mut copts = fs_extra::dir::CopyOptions::new();
-        copts.copy_inside = true;
-        copts.overwrite = true;
+    /// Restore the git history and composer.json of the plugin, which the
+    /// WP-CLI upgrade removes, while keeping the upgraded plugin files.
+    /// Mirrored plugins have their upgraded files synced into the mirror
+    /// instead.
+    fn restore_git_history(&mut self) -> Result<()> {
+        self.progress_log("Restoring history data and config");
 
-        if self.verbose {
-            self.progress_log(&format!("Working with backup directory `{:?}`", self.backup_dir));
-            self.progress_log(&format!("Copying files from `{:?}` to `{:?}`", backedup_gitdir, plugin_gitdir));
+        if self.dry_run {
+            return Ok(());
         }
 
-        plugin_gitdir.pop();
+        match self.mirror_dir {
+            Some(_) => self.sync_mirror()?,
+            None => {
+                let backup = self.get_backup()?;
+
+                self.backups.restore_git_dir(&backup, &self.plugin.get_plugin_dir())?;
+            }
+        };
+
+        self.write_composerjson_for_plugin()
+    }
 
-        let restore_result = fs_extra::dir::copy(&backedup_gitdir, &plugin_gitdir, &copts);
+    /// Sync the plugin directory into the mirror checkout of the plugin, if
+    /// the plugin is mirrored.
+    fn sync_mirror(&self) -> Result<()> {
+        let mirror_dir = match self.mirror_dir {
+            Some(ref d) => d,
+            None => return Ok(())
+        };
 
-        match restore_result {
-            Ok(_) => Ok(()),
-            Err(e) => Err(format!(
-                "Restoring backup failed for plugin `{}`: `{:?}`",
-                self.plugin.get_nicename(),
-                e
-            ))
+        self.progress_log(&format!("Syncing plugin files to mirror `{}`", mirror_dir.display()));
+
+        if self.dry_run {
+            return Ok(());
         }
+
+        sync_directory(&self.plugin.get_plugin_dir(), mirror_dir, &[".git"])
     }
 
     /// Update the designated plugin via WpCli.
-    fn update_plugin(&self) -> Result<bool, String> {
+    fn update_plugin(&self) -> Result<bool> {
         self.progress_log("Running WordPress update procedure");
 
         if self.dry_run {
@@ -310,3 +609,22 @@ impl Pipeline {
         return Ok(self.wp_cli.update_plugin(&self.plugin)?.contains("already updated") == false);
     }
 }
+
+/// Restore a plugin directory from a backup. The backup is verified and
+/// extracted next to the plugin directory, and replaces the plugin directory
+/// only if it contains an initialized git repository.
+pub fn restore_plugin_backup(git: &dyn VcsBackend, backups: &BackupStore, backup: &BackupEntry, plugin_dir: &Path) -> Result<()> {
+    let extracted = backups.extract(backup, plugin_dir)?;
+    let extracted_git = git.with_working_directory(extracted.clone());
+
+    if !extracted.join(".git").is_dir() || !extracted_git.repository_is_initialized()? {
+        backups.discard_extracted(backup, plugin_dir)?;
+
+        return Err(Error::Backup(format!(
+            "Backup `{}` does not contain an initialized git repository",
+            backup.id
+        )));
+    }
+
+    backups.replace_plugin_dir(backup, plugin_dir)
+}
@@ -0,0 +1,72 @@
+//! # policy
+//!
+//! Upgrade policies which restrict the plugin upgrades a pipeline accepts.
+
+use error::{Error, Result};
+use version::{Version, VersionBump};
+
+/// Upgrade policy of a single plugin.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UpgradePolicy {
+    /// Largest kind of version change which is upgraded to.
+    pub allow: VersionBump,
+    /// Highest version which is upgraded to.
+    pub max_version: Option<String>,
+}
+
+impl UpgradePolicy {
+    /// Does the policy restrict upgrades at all?
+    pub fn is_restricted(&self) -> bool {
+        self.allow != VersionBump::Major || self.max_version.is_some()
+    }
+
+    /// Get the parsed maximum version, if one has been set.
+    pub fn get_max_version(&self) -> Result<Option<Version>> {
+        match self.max_version {
+            Some(ref v) => match Version::parse(v) {
+                Ok(version) => Ok(Some(version)),
+                Err(_) => Err(Error::Version(format!("Invalid `max_version` `{}`", v)))
+            },
+            None => Ok(None)
+        }
+    }
+
+    /// Check an upgrade between two versions against the policy. Returns why
+    /// the upgrade is blocked, or `None` if the upgrade is allowed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wppr::policy::UpgradePolicy;
+    /// use wppr::version::{Version, VersionBump};
+    ///
+    /// let policy = UpgradePolicy {
+    ///     allow: VersionBump::Minor,
+    ///     max_version: Some("5.9".to_string()),
+    /// };
+    ///
+    /// let installed = Version::parse("5.2.1").unwrap();
+    ///
+    /// assert_eq!(None, policy.check(&installed, &Version::parse("5.3").unwrap()).unwrap());
+    /// assert!(policy.check(&installed, &Version::parse("5.10").unwrap()).unwrap().is_some());
+    /// assert!(policy.check(&installed, &Version::parse("6.0").unwrap()).unwrap().is_some());
+    /// ```
+    pub fn check(&self, from: &Version, to: &Version) -> Result<Option<String>> {
+        let bump = from.get_bump(to);
+
+        if bump > self.allow {
+            return Ok(Some(format!(
+                "{} upgrade from {} to {}, only {} upgrades are allowed",
+                bump.as_str(),
+                from,
+                to,
+                self.allow.as_str()
+            )));
+        }
+
+        match self.get_max_version()? {
+            Some(ref max) if to > max => Ok(Some(format!("{} is newer than `max_version` {}", to, max))),
+            _ => Ok(None)
+        }
+    }
+}
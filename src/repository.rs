@@ -0,0 +1,157 @@
+//! # repository
+//!
+//! Static Composer repository generation. Managed plugins are exported into a
+//! Satis-style `packages.json` which can be hosted on any static file server.
+
+use serde_json::{self, Map, Value};
+
+use std::{
+    fs::{copy, create_dir_all},
+    path::Path
+};
+
+use artifacts::{build_dist_archive, get_archive_path, get_checksum_path};
+use config::RuntimeConfig;
+use error::Result;
+use git::get_backend;
+use version::Version;
+use wordpress::Plugin;
+
+/// Name of the Composer repository index file.
+pub const PACKAGES_FILE_NAME: &str = "packages.json";
+
+/// Directory inside an exported repository which holds dist archives.
+pub const DIST_DIR_NAME: &str = "dist";
+
+/// Versions of a single plugin package in the repository.
+#[derive(Debug, Clone)]
+pub struct PackageVersions {
+    pub package_name: String,
+    pub versions: Map<String, Value>,
+    pub skipped_tags: Vec<String>,
+}
+
+/// Get all tagged versions of a plugin from its git repository. Tags which
+/// are not valid versions are skipped.
+pub fn get_package_versions(config: &RuntimeConfig, plugin: &Plugin) -> Result<PackageVersions> {
+    let git = get_backend(config.binaries.git.clone(), config.git.clone(), config.get_repository_dir(plugin)?);
+
+    let mut package = PackageVersions {
+        package_name: plugin.package_name.clone(),
+        versions: Map::new(),
+        skipped_tags: Vec::new(),
+    };
+
+    let mut tags: Vec<(Version, String)> = Vec::new();
+
+    for tag in git.list_tags()? {
+        match Version::parse(&tag) {
+            Ok(v) => tags.push((v, tag)),
+            Err(_) => package.skipped_tags.push(tag)
+        };
+    }
+
+    tags.sort_by(|a, b| a.0.cmp(&b.0));
+
+    for (version, tag) in tags {
+        let composerjson: Value = git
+            .get_file_at(&tag, "composer.json")?
+            .and_then(|c| serde_json::from_str(&c).ok())
+            .unwrap_or_else(|| Value::Object(Map::new()));
+
+        let mut package_version = match composerjson {
+            Value::Object(m) => m,
+            _ => Map::new()
+        };
+
+        let mut source = Map::new();
+
+        source.insert("type".to_string(), Value::from("git"));
+        source.insert("url".to_string(), Value::from(plugin.remote_repository.clone()));
+        source.insert("reference".to_string(), Value::from(git.get_commit_hash(&tag)?));
+
+        package_version.insert("name".to_string(), Value::from(plugin.package_name.clone()));
+        package_version.insert("version".to_string(), Value::from(tag.clone()));
+        package_version.insert("version_normalized".to_string(), Value::from(version.normalized()));
+        package_version.insert("source".to_string(), Value::Object(source));
+        package_version.insert("time".to_string(), Value::from(git.get_commit_time(&tag)?));
+
+        if !package_version.contains_key("type") {
+            package_version.insert("type".to_string(), Value::from("wordpress-plugin"));
+        }
+
+        package.versions.insert(tag, Value::Object(package_version));
+    }
+
+    Ok(package)
+}
+
+/// Get the URL of a dist archive in an exported repository. Without a
+/// configured repository URL a `file://` URL is used.
+fn get_dist_url(config: &RuntimeConfig, export_dir: &Path, archive_path: &Path) -> String {
+    let archive_url: Vec<String> = archive_path
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+
+    match config.repository.url {
+        Some(ref url) => format!("{}/{}/{}", url.trim_end_matches('/'), DIST_DIR_NAME, archive_url.join("/")),
+        None => format!("file://{}", export_dir.join(DIST_DIR_NAME).join(archive_path).display())
+    }
+}
+
+/// Add `dist` entries to package versions. Missing dist archives are built
+/// into the artifacts directory, and all archives are copied into the export
+/// directory.
+pub fn add_dist_entries(
+    config: &RuntimeConfig,
+    plugin: &Plugin,
+    package: &mut PackageVersions,
+    artifacts_dir: &Path,
+    export_dir: &Path,
+) -> Result<()> {
+    let git = get_backend(config.binaries.git.clone(), config.git.clone(), config.get_repository_dir(plugin)?);
+
+    for (tag, package_version) in package.versions.iter_mut() {
+        let archive = build_dist_archive(&*git, plugin, tag, artifacts_dir)?;
+        let archive_path = get_archive_path(&plugin.package_name, tag);
+        let exported_archive = export_dir.join(DIST_DIR_NAME).join(&archive_path);
+
+        if exported_archive != archive {
+            if let Some(parent) = exported_archive.parent() {
+                create_dir_all(parent)?;
+            }
+
+            copy(&archive, &exported_archive)?;
+            copy(get_checksum_path(&archive), get_checksum_path(&exported_archive))?;
+        }
+
+        let reference = package_version["source"]["reference"].clone();
+        let mut dist = Map::new();
+
+        dist.insert("type".to_string(), Value::from("zip"));
+        dist.insert("url".to_string(), Value::from(get_dist_url(config, export_dir, &archive_path)));
+        dist.insert("reference".to_string(), reference);
+
+        if let Value::Object(ref mut v) = *package_version {
+            v.insert("dist".to_string(), Value::Object(dist));
+        }
+    }
+
+    Ok(())
+}
+
+/// Build the contents of a `packages.json` from plugin packages.
+pub fn build_packages_json(packages: &[PackageVersions]) -> Value {
+    let mut package_map = Map::new();
+
+    for package in packages {
+        package_map.insert(package.package_name.clone(), Value::Object(package.versions.clone()));
+    }
+
+    let mut packages_json = Map::new();
+
+    packages_json.insert("packages".to_string(), Value::Object(package_map));
+
+    Value::Object(packages_json)
+}
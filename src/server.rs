@@ -0,0 +1,135 @@
+//! # server
+//!
+//! Minimal HTTP server for an exported Composer repository. Serves
+//! `packages.json` and the dist archives next to it, optionally behind HTTP
+//! basic auth.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha2::{Digest, Sha256};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use std::{
+    fs::File,
+    path::{Component, Path, PathBuf}
+};
+
+use config::BasicAuthConfig;
+use error::{Error, Result};
+use repository::{DIST_DIR_NAME, PACKAGES_FILE_NAME};
+
+/// Server for an exported Composer repository directory.
+pub struct RepositoryServer {
+    root: PathBuf,
+    authorization: Option<String>,
+}
+
+impl RepositoryServer {
+    /// Get a new server for a repository directory.
+    pub fn new(root: PathBuf, auth: Option<&BasicAuthConfig>) -> Self {
+        let authorization = auth.map(|a| {
+            format!("Basic {}", STANDARD.encode(format!("{}:{}", a.username, a.password)))
+        });
+
+        RepositoryServer {
+            root,
+            authorization,
+        }
+    }
+
+    /// Start listening on an address, e.g. `127.0.0.1:8080`.
+    pub fn bind(listen: &str) -> Result<Server> {
+        Server::http(listen).map_err(|e| Error::Server(format!("Cannot listen on `{}`: {}", listen, e)))
+    }
+
+    /// Handle incoming requests until the server is shut down.
+    pub fn serve(&self, server: &Server) {
+        for request in server.incoming_requests() {
+            self.handle_request(request);
+        }
+    }
+
+    /// Is a request with the given `Authorization` header value allowed?
+    pub fn is_authorized(&self, authorization: Option<&str>) -> bool {
+        match self.authorization {
+            Some(ref expected) => {
+                let given = authorization.map(|a| a.trim()).unwrap_or_default();
+
+                // digests have the same length whatever the credentials are
+                constant_time_eq(&Sha256::digest(given), &Sha256::digest(expected))
+            }
+            None => true
+        }
+    }
+
+    /// Get the file a request URL points to. Only `packages.json` and files
+    /// inside the dist directory are served.
+    pub fn resolve_path(&self, url: &str) -> Option<PathBuf> {
+        let path = Path::new(url.split('?').next().unwrap_or_default().trim_start_matches('/'));
+
+        if path.components().any(|c| !matches!(c, Component::Normal(_))) {
+            return None;
+        }
+
+        let servable = path == Path::new(PACKAGES_FILE_NAME) || path.starts_with(DIST_DIR_NAME);
+        let file = self.root.join(path);
+
+        match servable && file.is_file() {
+            true => Some(file),
+            false => None
+        }
+    }
+
+    /// Respond to a single request.
+    fn handle_request(&self, request: Request) {
+        let authorization = request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv("Authorization"))
+            .map(|h| h.value.as_str().to_string());
+
+        let result = match *request.method() {
+            Method::Get | Method::Head => match self.is_authorized(authorization.as_deref()) {
+                true => self.respond_with_file(request),
+                false => request.respond(
+                    Response::from_string("Unauthorized")
+                        .with_status_code(401)
+                        .with_header(get_header("WWW-Authenticate", "Basic realm=\"wppr\""))
+                ),
+            },
+            _ => request.respond(Response::from_string("Method not allowed").with_status_code(405)),
+        };
+
+        if let Err(e) = result {
+            eprintln!("Could not respond to request: {}", e);
+        }
+    }
+
+    /// Respond with the file a request points to, or a 404.
+    fn respond_with_file(&self, request: Request) -> ::std::io::Result<()> {
+        let file = self.resolve_path(request.url()).and_then(|f| File::open(&f).ok().map(|h| (f, h)));
+
+        match file {
+            Some((path, handle)) => {
+                let content_type = match path.extension().and_then(|e| e.to_str()) {
+                    Some("json") => "application/json",
+                    Some("zip") => "application/zip",
+                    _ => "text/plain; charset=utf-8",
+                };
+
+                request.respond(Response::from_file(handle).with_header(get_header("Content-Type", content_type)))
+            }
+            None => request.respond(Response::from_string("Not found").with_status_code(404)),
+        }
+    }
+}
+
+/// Get a response header. Header names and values used here are always valid.
+fn get_header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field.as_bytes(), value.as_bytes()).unwrap()
+}
+
+/// Compare byte strings without returning early, so a failed comparison does
+/// not reveal how much of a credential matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,182 @@
+//! # shell
+//!
+//! This module contains functionalities for running user defined shell
+//! commands, such as plugin pre-commands.
+
+use std::{
+    io::{self, Read},
+    os::unix::process::CommandExt,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant}
+};
+
+use libc;
+
+use error::{Error, Result};
+
+/// Default amount of seconds a single shell command is allowed to run.
+pub const DEFAULT_TIMEOUT: u64 = 300;
+
+/// Wrapper for a shell which runs user defined commands.
+pub struct Shell {
+    bin: String,
+    working_directory: PathBuf,
+    env: Vec<(String, String)>,
+    timeout: Duration
+}
+
+/// Captured results of a single shell command.
+#[derive(Debug, Clone)]
+pub struct ShellOutput {
+    pub command: String,
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: Option<i32>,
+    pub timed_out: bool
+}
+
+pub type ShellResult = Result<ShellOutput>;
+
+impl ShellOutput {
+    /// Did the command exit cleanly within the allowed time?
+    pub fn success(&self) -> bool {
+        !self.timed_out && self.exit_code == Some(0)
+    }
+
+    /// Get an error describing why the command failed, if it did.
+    pub fn error(&self) -> Option<Error> {
+        if self.success() {
+            return None;
+        }
+
+        Some(Error::Shell {
+            command: self.command.clone(),
+            code: self.exit_code,
+            stderr: self.stderr.clone(),
+            timed_out: self.timed_out
+        })
+    }
+}
+
+impl Shell {
+    /// Get a new shell wrapper instance. Timeout is given in seconds.
+    pub fn new(bin: String, cwd: PathBuf, timeout: u64) -> Self {
+        Shell {
+            bin,
+            working_directory: cwd,
+            env: Vec::new(),
+            timeout: Duration::from_secs(timeout)
+        }
+    }
+
+    /// Set an environment variable which is exposed to all commands run with
+    /// this shell.
+    pub fn set_env(&mut self, key: &str, value: &str) {
+        self.env.retain(|(k, _)| k != key);
+        self.env.push((key.to_string(), value.to_string()));
+    }
+
+    /// Run a single command with the shell, capturing its output. An error is
+    /// returned only if the command could not be started at all; failing
+    /// commands are reported via the returned output.
+    pub fn run(&self, command: &str) -> ShellResult {
+        let mut cmd = Command::new(&self.bin);
+
+        cmd.current_dir(&self.working_directory)
+            .args(["-c", command])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0);
+
+        for (key, value) in &self.env {
+            cmd.env(key, value);
+        }
+
+        let mut child = match cmd.spawn() {
+            Ok(c) => c,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(Error::MissingBinary {
+                    name: "shell".to_string(),
+                    bin: self.bin.clone()
+                });
+            }
+            Err(e) => return Err(Error::Io(e))
+        };
+
+        // read outputs in separate threads so a chatty command cannot block
+        // on a full pipe while we wait for it
+        let stdout_reader = spawn_reader(child.stdout.take());
+        let stderr_reader = spawn_reader(child.stderr.take());
+
+        let started = Instant::now();
+        let mut timed_out = false;
+
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => {
+                    // background children would keep the output pipes open
+                    // and the readers waiting after the command has exited
+                    kill_process_group(&mut child)?;
+
+                    break Some(status);
+                }
+                Ok(None) => (),
+                Err(e) => return Err(Error::Io(e))
+            };
+
+            if started.elapsed() >= self.timeout {
+                timed_out = true;
+
+                kill_process_group(&mut child)?;
+
+                break None;
+            }
+
+            thread::sleep(Duration::from_millis(20));
+        };
+
+        Ok(ShellOutput {
+            command: command.to_string(),
+            stdout: stdout_reader.join().unwrap_or_default(),
+            stderr: stderr_reader.join().unwrap_or_default(),
+            exit_code: status.and_then(|s| s.code()),
+            timed_out
+        })
+    }
+}
+
+/// Kill a child process and its process group, as commands may have spawned
+/// children of their own which would keep the output pipes open. The child is
+/// the leader of its own group, see `process_group(0)`.
+fn kill_process_group(child: &mut Child) -> Result<()> {
+    let killed = unsafe { libc::killpg(child.id() as libc::pid_t, libc::SIGKILL) };
+    let error = io::Error::last_os_error();
+
+    // the group is gone if the command and all of its children have exited
+    if killed != 0 && error.raw_os_error() != Some(libc::ESRCH) {
+        let _ = child.kill();
+        let _ = child.wait();
+
+        return Err(Error::Io(error));
+    }
+
+    child.wait()?;
+
+    Ok(())
+}
+
+/// Read a child process stream into a string in a separate thread.
+fn spawn_reader<R: Read + Send + 'static>(stream: Option<R>) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut contents: Vec<u8> = Vec::new();
+
+        if let Some(mut s) = stream {
+            let _ = s.read_to_end(&mut contents);
+        }
+
+        String::from_utf8_lossy(&contents).to_string()
+    })
+}
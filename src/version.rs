@@ -0,0 +1,271 @@
+//! # version
+//!
+//! Parsing, normalization, and comparison of plugin versions.
+//!
+//! Plugin authors are not bound to any versioning scheme, so versions like
+//! `5.12`, `2.7.1.3`, `1.0.0-beta2`, or `v3.1` are all accepted. Versions are
+//! compared the same way Composer compares them.
+
+use regex::Regex;
+
+use std::{cmp::Ordering, fmt, sync::OnceLock};
+
+use error::{Error, Result};
+
+/// How many numeric parts a version can have at most.
+const MAX_VERSION_NUMBERS: usize = 4;
+
+/// Stability of a version, in ascending order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stability {
+    Dev,
+    Alpha,
+    Beta,
+    RC,
+    Stable,
+    Patch,
+}
+
+impl Stability {
+    /// Get a stability from a version suffix, e.g. `b` or `beta`.
+    fn from_suffix(suffix: &str) -> Stability {
+        match suffix.to_lowercase().as_str() {
+            "alpha" | "a" => Stability::Alpha,
+            "beta" | "b" => Stability::Beta,
+            "rc" => Stability::RC,
+            "patch" | "pl" | "p" => Stability::Patch,
+            _ => Stability::Stable,
+        }
+    }
+
+    /// Get the name of the stability used in normalized versions.
+    fn get_name(&self) -> &'static str {
+        match *self {
+            Stability::Dev => "dev",
+            Stability::Alpha => "alpha",
+            Stability::Beta => "beta",
+            Stability::RC => "RC",
+            Stability::Stable => "stable",
+            Stability::Patch => "patch",
+        }
+    }
+}
+
+/// Kind of change between two versions, in ascending order of size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionBump {
+    /// Only the third or fourth version number, or the stability, changed.
+    Patch,
+    /// The second version number changed.
+    Minor,
+    /// The first version number changed.
+    #[default]
+    Major,
+}
+
+impl VersionBump {
+    /// Get the bump as it is written in configuration files.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            VersionBump::Patch => "patch",
+            VersionBump::Minor => "minor",
+            VersionBump::Major => "major",
+        }
+    }
+}
+
+/// A parsed plugin version.
+#[derive(Debug, Clone)]
+pub struct Version {
+    original: String,
+    numbers: Vec<u64>,
+    stability: Stability,
+    stability_numbers: Vec<u64>,
+    dev: bool,
+}
+
+impl Version {
+    /// Parse a version string.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wppr::version::Version;
+    ///
+    /// let version = Version::parse("v3.1-beta2").unwrap();
+    ///
+    /// assert_eq!("3.1.0.0-beta2", version.normalized());
+    /// assert_eq!("3.1-beta2", version.to_tag());
+    /// assert!(version < Version::parse("3.1").unwrap());
+    /// assert!(Version::parse("latest").is_err());
+    /// ```
+    pub fn parse(version: &str) -> Result<Version> {
+        let trimmed = version.trim();
+        let invalid = || Error::Version(format!("`{}` is not a valid version", trimmed));
+
+        let captures = match get_version_matcher().captures(trimmed) {
+            Some(c) => c,
+            None => return Err(invalid())
+        };
+
+        let numbers = match parse_numbers(captures.get(1).map(|m| m.as_str()).unwrap_or_default()) {
+            Some(n) => n,
+            None => return Err(invalid())
+        };
+
+        if numbers.len() > MAX_VERSION_NUMBERS {
+            return Err(invalid());
+        }
+
+        let stability_numbers = match parse_numbers(captures.get(3).map(|m| m.as_str()).unwrap_or_default()) {
+            Some(n) => n,
+            None => return Err(invalid())
+        };
+
+        let dev = captures.get(4).is_some();
+
+        let stability = match captures.get(2) {
+            Some(s) => Stability::from_suffix(s.as_str()),
+            None if dev => Stability::Dev,
+            None => Stability::Stable
+        };
+
+        Ok(Version {
+            original: trimmed.to_string(),
+            numbers,
+            stability,
+            stability_numbers,
+            dev: dev && stability != Stability::Dev,
+        })
+    }
+
+    /// Get the version as it was originally given.
+    pub fn as_str(&self) -> &str {
+        &self.original
+    }
+
+    /// Get the stability of the version.
+    pub fn get_stability(&self) -> Stability {
+        self.stability
+    }
+
+    /// Get the numeric parts of the version, padded to four parts.
+    fn get_padded_numbers(&self) -> Vec<u64> {
+        let mut numbers = self.numbers.clone();
+
+        numbers.resize(MAX_VERSION_NUMBERS, 0);
+
+        numbers
+    }
+
+    /// Get the stability suffix of the version, e.g. `-beta2`.
+    fn get_suffix(&self) -> String {
+        let mut suffix = String::new();
+
+        if self.stability != Stability::Stable && self.stability != Stability::Dev {
+            let numbers: Vec<String> = self.stability_numbers.iter().map(|n| n.to_string()).collect();
+
+            suffix.push_str(&format!("-{}{}", self.stability.get_name(), numbers.join(".")));
+        }
+
+        if self.dev || self.stability == Stability::Dev {
+            suffix.push_str("-dev");
+        }
+
+        suffix
+    }
+
+    /// Get the version normalized the way Composer normalizes versions, e.g.
+    /// `5.12` becomes `5.12.0.0`.
+    pub fn normalized(&self) -> String {
+        let numbers: Vec<String> = self.get_padded_numbers().iter().map(|n| n.to_string()).collect();
+
+        format!("{}{}", numbers.join("."), self.get_suffix())
+    }
+
+    /// Get the kind of change from this version to another.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wppr::version::{Version, VersionBump};
+    ///
+    /// let version = Version::parse("5.2.1").unwrap();
+    ///
+    /// assert_eq!(VersionBump::Patch, version.get_bump(&Version::parse("5.2.3").unwrap()));
+    /// assert_eq!(VersionBump::Minor, version.get_bump(&Version::parse("5.3").unwrap()));
+    /// assert_eq!(VersionBump::Major, version.get_bump(&Version::parse("6.0-beta1").unwrap()));
+    /// ```
+    pub fn get_bump(&self, other: &Version) -> VersionBump {
+        let numbers = self.get_padded_numbers();
+        let other_numbers = other.get_padded_numbers();
+
+        if numbers[0] != other_numbers[0] {
+            VersionBump::Major
+        } else if numbers[1] != other_numbers[1] {
+            VersionBump::Minor
+        } else {
+            VersionBump::Patch
+        }
+    }
+
+    /// Get the version formatted for a git tag that Composer understands,
+    /// e.g. `v3.1b2` becomes `3.1-beta2`.
+    pub fn to_tag(&self) -> String {
+        let numbers: Vec<String> = self.numbers.iter().map(|n| n.to_string()).collect();
+
+        format!("{}{}", numbers.join("."), self.get_suffix())
+    }
+}
+
+/// Get the matcher of version strings. It is compiled once, as versions are
+/// parsed for every tag of every plugin.
+fn get_version_matcher() -> &'static Regex {
+    static MATCHER: OnceLock<Regex> = OnceLock::new();
+
+    MATCHER.get_or_init(|| {
+        Regex::new(
+            r"^(?i)v?(\d+(?:\.\d+)*)(?:[._-]?(stable|beta|b|rc|alpha|a|patch|pl|p)((?:[.-]?\d+)*))?([.-]?dev)?$"
+        ).unwrap()
+    })
+}
+
+/// Parse numbers separated with dots or dashes, e.g. `1.2.3`.
+fn parse_numbers(numbers: &str) -> Option<Vec<u64>> {
+    numbers
+        .split(['.', '-'])
+        .filter(|n| !n.is_empty())
+        .map(|n| n.parse::<u64>().ok())
+        .collect()
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Version) -> Ordering {
+        self.get_padded_numbers()
+            .cmp(&other.get_padded_numbers())
+            .then(self.stability.cmp(&other.stability))
+            .then(self.stability_numbers.cmp(&other.stability_numbers))
+            .then(other.dev.cmp(&self.dev))
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Version) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Version) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.original)
+    }
+}
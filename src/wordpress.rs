@@ -2,16 +2,24 @@
 //!
 //! This module contains WordPress specific functionalities.
 
-use regex::Regex;
+use regex::{self, Regex};
+use serde_json::{self, Value};
 
 use std::{
+    collections::HashMap,
     process::Command,
     path::PathBuf,
     fs::File,
-    io::Read
+    io::{self, Read},
+    path::Path,
+    sync::OnceLock
 };
 
-use config::PluginConfig;
+use config::{ComposerStrategy, PluginConfig, PluginHold};
+use error::{Error, Result};
+use policy::UpgradePolicy;
+use shell::DEFAULT_TIMEOUT;
+use version::Version;
 
 /// Representation of a WP plugin.
 #[derive(Debug, Clone)]
@@ -21,7 +29,14 @@ pub struct Plugin {
     pub remote_repository: String,
     pub installed_version: Option<String>,
     pub nicename: Option<String>,
-    pub pre_cmds: Vec<String>
+    pub header: Option<PluginHeader>,
+    pub pre_cmds: Vec<String>,
+    pub post_cmds: Vec<String>,
+    pub cmds_timeout: u64,
+    pub composer_overrides: Option<Value>,
+    pub composer_strategy: ComposerStrategy,
+    pub hold: Option<PluginHold>,
+    pub policy: UpgradePolicy
 }
 
 impl Plugin {
@@ -31,6 +46,8 @@ impl Plugin {
     ///
     /// ```
     /// use wppr::wordpress;
+    /// use wppr::config::ComposerStrategy;
+    /// use wppr::policy::UpgradePolicy;
     /// use std::path::PathBuf;
     ///
     /// let plugin = wordpress::Plugin {
@@ -39,7 +56,14 @@ impl Plugin {
     ///     remote_repository: "".to_string(),
     ///     installed_version: Some("1.2.3".to_string()),
     ///     nicename: None,
-    ///     pre_cmds: Vec::new()
+    ///     header: None,
+    ///     pre_cmds: Vec::new(),
+    ///     post_cmds: Vec::new(),
+    ///     cmds_timeout: 300,
+    ///     composer_overrides: None,
+    ///     composer_strategy: ComposerStrategy::Keep,
+    ///     hold: None,
+    ///     policy: UpgradePolicy::default()
     /// };
     ///
     /// let failing_plugin = wordpress::Plugin {
@@ -48,20 +72,43 @@ impl Plugin {
     ///     remote_repository: "".to_string(),
     ///     installed_version: None,
     ///     nicename: None,
-    ///     pre_cmds: Vec::new()
+    ///     header: None,
+    ///     pre_cmds: Vec::new(),
+    ///     post_cmds: Vec::new(),
+    ///     cmds_timeout: 300,
+    ///     composer_overrides: None,
+    ///     composer_strategy: ComposerStrategy::Keep,
+    ///     hold: None,
+    ///     policy: UpgradePolicy::default()
     /// };
     ///
     /// assert_eq!(true, plugin.is_valid());
     /// assert_eq!(false, failing_plugin.is_valid());
     pub fn is_valid(&self) -> bool {
-        let valid_version = match self.installed_version {
-            Some(_) => true,
-            None => false,
-        };
+        self.validate().is_ok()
+    }
 
-        let valid_index = self.index_path.exists();
+    /// Validates the plugin, erroring with the reason the plugin cannot be
+    /// managed.
+    pub fn validate(&self) -> Result<()> {
+        if !self.index_path.exists() {
+            return Err(Error::Plugin(format!(
+                "Plugin index file `{}` does not exist",
+                self.index_path.display()
+            )));
+        }
 
-        valid_version && valid_index
+        match self.installed_version {
+            Some(ref v) => Version::parse(v)?,
+            None => {
+                return Err(Error::Version(format!(
+                    "No `Version` header found in `{}`",
+                    self.index_path.display()
+                )));
+            }
+        };
+
+        self.policy.get_max_version().map(|_| ())
     }
 
     /// Get the nicename of this plugin, e.g. `dir/index.php`.
@@ -73,61 +120,73 @@ impl Plugin {
     }
 
     /// Get a WpCli usable plugin dir name.
-    pub fn get_cli_name(&self) -> Result<String, String> {
+    pub fn get_cli_name(&self) -> Result<String> {
         let nicename = self.get_nicename();
 
         if nicename == "invalid" {
-            return Err("invalid".to_string());
+            return Err(Error::Plugin("Cannot get a WP-CLI name for an invalid plugin".to_string()));
         }
 
-        let nicepath = PathBuf::from(nicename);
-        let mut niceiter = nicepath.iter();
-        let dir = niceiter.nth(0).unwrap();
+        let nicepath = PathBuf::from(&nicename);
 
-        return Ok(dir.to_os_string().into_string().unwrap());
+        match nicepath.iter().nth(0).and_then(|d| d.to_str()) {
+            Some(dir) => Ok(dir.to_string()),
+            None => Err(Error::Plugin(format!("Cannot get a WP-CLI name for plugin `{}`", nicename)))
+        }
     }
 
     pub fn from_config(plugin_config: PluginConfig, config_dir: &PathBuf) -> Plugin {
-        let absolute_index_path = format!(
-            "{}/{}",
-            config_dir.to_str().unwrap(),
-            plugin_config.index_path
-        );
+        let absolute_index_path = config_dir.join(&plugin_config.index_path);
 
         let mut plugin: Plugin = Plugin {
-            index_path: PathBuf::from(absolute_index_path),
+            index_path: absolute_index_path,
             package_name: plugin_config.package_name,
             remote_repository: plugin_config.remote_repository,
             installed_version: None,
             nicename: None,
-            pre_cmds: plugin_config.pre_cmds.unwrap_or(Vec::new())
+            header: None,
+            pre_cmds: plugin_config.pre_cmds.unwrap_or_default(),
+            post_cmds: plugin_config.post_cmds.unwrap_or_default(),
+            cmds_timeout: plugin_config.cmds_timeout.unwrap_or(DEFAULT_TIMEOUT),
+            composer_overrides: plugin_config.composer.and_then(|c| serde_json::to_value(c).ok()),
+            composer_strategy: plugin_config.composer_strategy.unwrap_or_default(),
+            hold: plugin_config.hold.filter(|h| h.is_held()),
+            policy: UpgradePolicy {
+                allow: plugin_config.allow.unwrap_or_default(),
+                max_version: plugin_config.max_version
+            }
         };
 
         let nicename = get_plugin_nicename(&plugin);
-        let installed_version = get_plugin_version(&plugin);
+        let header = PluginHeader::from_file(&plugin.index_path).ok();
 
         plugin.nicename = Some(nicename);
-
-        plugin.installed_version = match installed_version {
-            Ok(v) => Some(v),
-            Err(_) => None
-        };
+        plugin.installed_version = header.as_ref().and_then(|h| h.version.clone());
+        plugin.header = header;
 
         plugin
     }
 
+    /// Get the directory the plugin resides in.
+    pub fn get_plugin_dir(&self) -> PathBuf {
+        let mut plugin_dir = self.index_path.clone();
+        plugin_dir.pop();
+
+        plugin_dir
+    }
+
     /// Get a .git directory which is inside the plugin directory.
-    pub fn get_git_dir(&self) -> Result<PathBuf, String> {
+    pub fn get_git_dir(&self) -> Result<PathBuf> {
         let gitdir = self.get_git_dir_path();
 
         if gitdir.exists() && gitdir.is_dir() {
             return Ok(gitdir);
         }
 
-        return Err(format!(
+        return Err(Error::Plugin(format!(
             "Cannot fetch git directory for plugin `{}`, not a directory",
-            self.nicename.clone().unwrap_or("invalid".to_string())
-        ));
+            self.get_nicename()
+        )));
     }
 
     /// Get the path to the plugins git directory.
@@ -140,17 +199,17 @@ impl Plugin {
     }
 
     /// Get a composer.json which exists inside the plugin directory.
-    pub fn get_composerjson_file(&self) -> Result<PathBuf, String> {
+    pub fn get_composerjson_file(&self) -> Result<PathBuf> {
         let cjson = self.get_composerjson_path();
 
         if cjson.exists() {
             return Ok(cjson);
         }
 
-        return Err(format!(
+        return Err(Error::Plugin(format!(
             "Cannot fetch composer.json for plugin `{}`, does not exist",
-            self.nicename.clone().unwrap_or("invalid".to_string())
-        ));
+            self.get_nicename()
+        )));
     }
 
     /// Get the file path to the plugins composer.json file.
@@ -167,48 +226,210 @@ impl Plugin {
 fn get_plugin_nicename(plugin: &Plugin) -> String {
     let path: PathBuf = plugin.index_path.to_owned();
 
-    let nicenameparts: Vec<&str> = vec![
+    let nicenameparts: Vec<String> = vec![
         path.parent()
-            .unwrap()
-            .file_name()
-            .unwrap()
-            .to_str()
-            .unwrap(),
-        path.file_name().unwrap().to_str().unwrap(),
+            .and_then(|p| p.file_name())
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        path.file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default(),
     ];
 
     nicenameparts.join("/")
 }
 
-fn get_plugin_index_file_contents(index_path: &PathBuf) -> Result<String, String> {
-    let mut contents: String = String::new();
+/// How many bytes from the beginning of a plugin index file are searched for
+/// header data. Matches what WordPress reads in `get_file_data()`.
+const PLUGIN_HEADER_MAX_BYTES: u64 = 8192;
+
+/// Standard header data of a WordPress plugin, read from the comment block
+/// at the beginning of the plugin index file. Missing and empty headers are
+/// `None`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PluginHeader {
+    pub name: Option<String>,
+    pub plugin_uri: Option<String>,
+    pub description: Option<String>,
+    pub version: Option<String>,
+    pub requires_wp: Option<String>,
+    pub requires_php: Option<String>,
+    pub author: Option<String>,
+    pub author_uri: Option<String>,
+    pub license: Option<String>,
+    pub license_uri: Option<String>,
+    pub text_domain: Option<String>,
+    pub domain_path: Option<String>,
+    pub network: bool,
+    pub update_uri: Option<String>,
+    pub requires_plugins: Vec<String>,
+}
+
+impl PluginHeader {
+    /// Read the header of a plugin index file.
+    pub fn from_file(index_path: &Path) -> Result<PluginHeader> {
+        if !index_path.exists() {
+            return Err(Error::Plugin(format!(
+                "Could not read plugin index file `{}`, file does not exist",
+                index_path.display()
+            )));
+        }
+
+        let mut data: Vec<u8> = Vec::new();
+
+        File::open(index_path)?
+            .take(PLUGIN_HEADER_MAX_BYTES)
+            .read_to_end(&mut data)?;
 
-    if index_path.exists() == false {
-        return Err("Could not read file contents, file does not exist".to_string());
+        Ok(PluginHeader::parse(&String::from_utf8_lossy(&data)))
     }
 
-    File::open(index_path)
-        .unwrap()
-        .read_to_string(&mut contents)
-        .unwrap();
+    /// Parse header data from plugin index file contents the same way
+    /// WordPress does.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wppr::wordpress::PluginHeader;
+    ///
+    /// let header = PluginHeader::parse("<?php\n/**\n * Plugin Name: Hello\n * Version: 1.2.3.4\n */");
+    ///
+    /// assert_eq!(Some("Hello".to_string()), header.name);
+    /// assert_eq!(Some("1.2.3.4".to_string()), header.version);
+    /// assert_eq!(None, header.author);
+    /// ```
+    pub fn parse(contents: &str) -> PluginHeader {
+        let contents = contents.replace('\r', "\n");
+        let get = |name: &str| get_header_value(&contents, name);
+
+        PluginHeader {
+            name: get("Plugin Name"),
+            plugin_uri: get("Plugin URI"),
+            description: get("Description"),
+            version: get("Version"),
+            requires_wp: get("Requires at least"),
+            requires_php: get("Requires PHP"),
+            author: get("Author"),
+            author_uri: get("Author URI"),
+            license: get("License"),
+            license_uri: get("License URI"),
+            text_domain: get("Text Domain"),
+            domain_path: get("Domain Path"),
+            network: get("Network").map(|n| n.to_lowercase() == "true").unwrap_or(false),
+            update_uri: get("Update URI"),
+            requires_plugins: get("Requires Plugins")
+                .map(|r| {
+                    r.split(',')
+                        .map(|p| p.trim().to_string())
+                        .filter(|p| !p.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Names of the plugin headers read from plugin index files.
+const HEADER_NAMES: [&str; 15] = [
+    "Plugin Name",
+    "Plugin URI",
+    "Description",
+    "Version",
+    "Requires at least",
+    "Requires PHP",
+    "Author",
+    "Author URI",
+    "License",
+    "License URI",
+    "Text Domain",
+    "Domain Path",
+    "Network",
+    "Update URI",
+    "Requires Plugins",
+];
+
+/// Get the matchers of plugin header lines by header name. They are compiled
+/// once, as every managed plugin header is parsed with them.
+fn get_header_matchers() -> &'static HashMap<&'static str, Regex> {
+    static MATCHERS: OnceLock<HashMap<&'static str, Regex>> = OnceLock::new();
+
+    MATCHERS.get_or_init(|| {
+        HEADER_NAMES
+            .iter()
+            .map(|name| {
+                let pattern = format!(r"(?mi)^(?:[ \t]*<\?php)?[ \t/*#@]*{}:(.*)$", regex::escape(name));
+
+                (*name, Regex::new(&pattern).unwrap())
+            })
+            .collect()
+    })
+}
+
+/// Get the matcher of trailing comment closers in header values.
+fn get_header_cleaner() -> &'static Regex {
+    static CLEANER: OnceLock<Regex> = OnceLock::new();
+
+    CLEANER.get_or_init(|| Regex::new(r"\s*(?:\*/|\?>).*").unwrap())
+}
+
+/// Get the value of a single header from plugin file contents, cleaning up
+/// trailing comment closers like WordPress' `_cleanup_header_comment()`.
+/// Only headers in `HEADER_NAMES` are read.
+fn get_header_value(contents: &str, name: &str) -> Option<String> {
+    let matcher = get_header_matchers().get(name)?;
+
+    let value = matcher.captures(contents).and_then(|c| c.get(1))?.as_str();
+    let value = get_header_cleaner().replace(value, "").trim().to_string();
 
-    Ok(contents)
+    match value.is_empty() {
+        true => None,
+        false => Some(value)
+    }
 }
 
-pub fn get_plugin_version(plugin: &Plugin) -> Result<String, String> {
-    let index_contents: String = get_plugin_index_file_contents(&plugin.index_path)?;
+/// Read the current version of a plugin from its index file header.
+pub fn get_plugin_version(plugin: &Plugin) -> Result<String> {
+    match PluginHeader::from_file(&plugin.index_path)?.version {
+        Some(v) => Ok(v),
+        None => Err(Error::Version(format!(
+            "No `Version` header found in `{}`",
+            plugin.index_path.display()
+        )))
+    }
+}
 
-    let version_matcher = Regex::new(r"Version:\s+(\d+\.\d+\.\d+)\s+").unwrap();
+/// A plugin installed in WordPress as reported by `wp plugin list`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct InstalledPlugin {
+    pub name: String,
+    pub status: String,
+    pub version: String,
+    pub file: String,
+    pub wporg_status: Option<String>,
+}
 
-    let version = version_matcher
-        .captures(&index_contents)
-        .unwrap()
-        .get(1)
-        .unwrap()
-        .as_str()
-        .to_string();
+impl InstalledPlugin {
+    /// Is the plugin available in the wordpress.org plugin directory?
+    pub fn is_on_wporg(&self) -> bool {
+        match self.wporg_status {
+            Some(ref s) => s == "active" || s == "closed",
+            None => false
+        }
+    }
+
+    /// Does the plugin reside in its own directory? Single file plugins and
+    /// must-use plugins cannot be managed.
+    pub fn has_plugin_dir(&self) -> bool {
+        self.file.contains('/') && self.status != "must-use" && self.status != "dropin"
+    }
+}
 
-    Ok(version)
+/// A plugin update available in WordPress, as listed by WP-CLI.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PluginUpdate {
+    pub name: String,
+    pub version: String,
+    pub update_version: Option<String>,
 }
 
 /// WpCli wrapper.
@@ -217,7 +438,7 @@ pub struct WpCli {
     working_directory: PathBuf
 }
 
-pub type WpCliResult = Result<String, String>;
+pub type WpCliResult = Result<String>;
 
 impl WpCli {
     /// Get a new WpCli wrapper instance.
@@ -244,17 +465,106 @@ impl WpCli {
     pub fn update_plugin(&self, plugin: &Plugin) -> WpCliResult {
         let pname = plugin.get_cli_name()?;
 
-        let mut cmd = self.get_base_command();
+        self.run_checked_cmd(&["plugin", "update", &pname])
+    }
 
-        cmd.args(&["plugin", "update", &pname]);
+    /// Get the version WordPress offers as an update for a plugin. Returns
+    /// `None` if no update is available or its version is unknown.
+    pub fn get_update_version(&self, plugin: &Plugin) -> Result<Option<String>> {
+        let name_filter = format!("--name={}", plugin.get_cli_name()?);
+        let args = ["plugin", "list", "--format=json", &name_filter, "--fields=name,update,update_version"];
+
+        let output = self.run_checked_cmd(&args)?;
+
+        let plugins: Vec<Value> = match serde_json::from_str(&output) {
+            Ok(p) => p,
+            Err(e) => {
+                return Err(Error::WpCli {
+                    command: format!("wp {}", args.join(" ")),
+                    code: Some(0),
+                    stderr: format!("Could not parse plugin list: {}", e)
+                });
+            }
+        };
 
-        let output = cmd.output().expect("Error when trying to run wp-cli command: `wp update plugin ...`");
+        let update = plugins.first().and_then(|p| {
+            match p.get("update").and_then(|u| u.as_str()) {
+                Some("available") => p.get("update_version").and_then(|v| v.as_str()),
+                _ => None
+            }
+        });
+
+        Ok(update.filter(|v| !v.is_empty()).map(|v| v.to_string()))
+    }
+
+    /// List plugins installed in WordPress. Requesting wordpress.org status
+    /// data requires network access and a recent WpCli version.
+    pub fn list_plugins(&self, with_wporg_status: bool) -> Result<Vec<InstalledPlugin>> {
+        let fields = match with_wporg_status {
+            true => "--fields=name,status,version,file,wporg_status",
+            false => "--fields=name,status,version,file"
+        };
+
+        let output = self.run_checked_cmd(&["plugin", "list", "--format=json", fields])?;
+
+        match serde_json::from_str(&output) {
+            Ok(plugins) => Ok(plugins),
+            Err(e) => Err(Error::WpCli {
+                command: format!("wp plugin list --format=json {}", fields),
+                code: Some(0),
+                stderr: format!("Could not parse plugin list: {}", e)
+            })
+        }
+    }
+
+    /// List plugins which have an update available in WordPress.
+    pub fn list_plugin_updates(&self) -> Result<Vec<PluginUpdate>> {
+        let args = ["plugin", "list", "--update=available", "--format=json", "--fields=name,version,update_version"];
+
+        let output = self.run_checked_cmd(&args)?;
+
+        match serde_json::from_str(&output) {
+            Ok(updates) => Ok(updates),
+            Err(e) => Err(Error::WpCli {
+                command: format!("wp {}", args.join(" ")),
+                code: Some(0),
+                stderr: format!("Could not parse plugin list: {}", e)
+            })
+        }
+    }
+
+    /// Get the absolute path to the WordPress plugins directory.
+    pub fn get_plugins_dir(&self) -> Result<PathBuf> {
+        let output = self.run_checked_cmd(&["plugin", "path"])?;
+
+        Ok(PathBuf::from(output.trim()))
+    }
+
+    /// Run a WpCli command with the given arguments and return its stdout,
+    /// erroring if the command exits unsuccessfully.
+    fn run_checked_cmd(&self, args: &[&str]) -> WpCliResult {
+        let mut cmd = self.get_base_command();
+
+        cmd.args(args);
+
+        let output = match cmd.output() {
+            Ok(o) => o,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(Error::MissingBinary {
+                    name: "wp cli".to_string(),
+                    bin: self.bin.clone()
+                });
+            },
+            Err(e) => return Err(Error::Io(e))
+        };
 
         match output.status.success() {
             true => Ok(String::from_utf8_lossy(&output.stdout).to_string()),
-            false => {
-                Err(format!("Could not update plugin `{}`: `{}`", pname, String::from_utf8_lossy(&output.stderr)))
-            }
+            false => Err(Error::WpCli {
+                command: format!("wp {}", args.join(" ")),
+                code: output.status.code(),
+                stderr: String::from_utf8_lossy(&output.stderr).to_string()
+            })
         }
     }
 }
@@ -271,7 +581,14 @@ mod tests {
             package_name: "".to_string(),
             installed_version: None,
             nicename: None,
-            pre_cmds: Vec::new()
+            header: None,
+            pre_cmds: Vec::new(),
+            post_cmds: Vec::new(),
+            cmds_timeout: 300,
+            composer_overrides: None,
+            composer_strategy: ComposerStrategy::Keep,
+            hold: None,
+            policy: UpgradePolicy::default()
         };
 
         let nicename: String = get_plugin_nicename(&plugin);
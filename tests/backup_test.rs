@@ -0,0 +1,178 @@
+extern crate wppr;
+
+use std::fs::{create_dir_all, read_to_string, remove_dir_all, remove_file, write};
+use std::path::{Path, PathBuf};
+
+use wppr::backup::*;
+use wppr::commands::{backup_prune, backup_restore};
+use wppr::config::{PluginConfig, RuntimeConfig};
+use wppr::wordpress::Plugin;
+
+#[path = "./testfns.rs"]
+mod testfns;
+
+use testfns::{get_test_dir, git, write_plugin_version, write_test_config, MY_PLUGIN};
+
+fn setup_test_plugin(name: &str) -> (PathBuf, Plugin) {
+    let project_dir = get_test_dir(name);
+    let plugin_dir = project_dir.join("my-plugin");
+
+    create_dir_all(plugin_dir.join(".git/refs")).unwrap();
+
+    write_plugin_version(&plugin_dir, "1.0.0");
+    write(plugin_dir.join("readme.txt"), "Original readme").unwrap();
+    write(plugin_dir.join(".git/HEAD"), "ref: refs/heads/master\n").unwrap();
+
+    let plugin_config = PluginConfig {
+        index_path: "my-plugin/my-plugin.php".to_string(),
+        package_name: "my-agency/my-plugin".to_string(),
+        remote_repository: "git@example.com:my-agency/my-plugin.git".to_string(),
+        pre_cmds: None,
+        post_cmds: None,
+        cmds_timeout: None,
+        composer: None,
+        composer_strategy: None,
+        hold: None,
+        allow: None,
+        max_version: None,
+    };
+
+    let plugin = Plugin::from_config(plugin_config, &project_dir);
+
+    (project_dir, plugin)
+}
+
+fn get_test_config(project_dir: &Path) -> RuntimeConfig {
+    write_test_config(project_dir, "", MY_PLUGIN);
+
+    testfns::get_test_config(project_dir)
+}
+
+#[test]
+fn test_backups_are_indexed_and_pruned() {
+    let (project_dir, plugin) = setup_test_plugin("backup-prune-test");
+    let store = BackupStore::new(project_dir.join(".wpprbackups"));
+
+    let first = store.create(&plugin, Some("abc123".to_string())).unwrap();
+    let second = store.create(&plugin, None).unwrap();
+    let third = store.create(&plugin, None).unwrap();
+
+    let index = store.load_index().unwrap();
+    let first_archive = store.get_archive_path(&first);
+    let first_archive_existed = first_archive.exists();
+
+    let removed = store.prune("my-plugin", 2).unwrap();
+    let remaining = store.get_plugin_backups("my-plugin").unwrap();
+    let first_archive_exists = first_archive.exists();
+
+    remove_dir_all(&project_dir).unwrap();
+
+    assert_eq!(3, index.len());
+    assert_eq!("my-plugin", first.plugin);
+    assert_eq!("my-agency/my-plugin", first.package_name);
+    assert_eq!(Some("1.0.0".to_string()), first.version);
+    assert_eq!(Some("abc123".to_string()), first.commit);
+    assert_eq!(3, first.files);
+    assert!(first.file.starts_with("my-plugin/my-plugin-"));
+    assert!(first.file.ends_with(".tar.gz"));
+    assert_ne!(first.id, second.id);
+    assert_ne!(second.id, third.id);
+
+    assert!(first_archive_existed);
+    assert!(!first_archive_exists);
+    assert_eq!(vec![first], removed);
+    assert_eq!(vec![second, third], remaining);
+}
+
+#[test]
+fn test_backups_are_restored() {
+    let (project_dir, plugin) = setup_test_plugin("backup-restore-test");
+    let plugin_dir = plugin.get_plugin_dir();
+    let store = BackupStore::new(project_dir.join(".wpprbackups"));
+
+    let backup = store.create(&plugin, None).unwrap();
+
+    // an upgrade replaces plugin files and removes the git directory
+    remove_dir_all(plugin_dir.join(".git")).unwrap();
+    write(plugin_dir.join("readme.txt"), "Upgraded readme").unwrap();
+    write(plugin_dir.join("new-file.php"), "<?php\n").unwrap();
+
+    let git_restored = store.restore_git_dir(&backup, &plugin_dir);
+    let git_head = read_to_string(plugin_dir.join(".git/HEAD")).unwrap();
+    let readme_after_git_restore = read_to_string(plugin_dir.join("readme.txt")).unwrap();
+
+    let restored = store.restore(&backup, &plugin_dir);
+    let readme_after_restore = read_to_string(plugin_dir.join("readme.txt")).unwrap();
+    let new_file_exists = plugin_dir.join("new-file.php").exists();
+    let staging_exists = project_dir.join(".wppr-restore-my-plugin").exists();
+
+    // a corrupted archive is never restored
+    write(store.get_archive_path(&backup), "corrupted").unwrap();
+    let corrupted = store.restore(&backup, &plugin_dir);
+
+    remove_file(store.get_archive_path(&backup)).unwrap();
+    let missing = store.restore(&backup, &plugin_dir);
+    let plugin_still_exists = plugin_dir.join("my-plugin.php").exists();
+
+    remove_dir_all(&project_dir).unwrap();
+
+    assert!(git_restored.is_ok());
+    assert_eq!("ref: refs/heads/master\n", git_head);
+    assert_eq!("Upgraded readme", readme_after_git_restore);
+
+    assert!(restored.is_ok());
+    assert_eq!("Original readme", readme_after_restore);
+    assert!(!new_file_exists);
+    assert!(!staging_exists);
+
+    assert!(corrupted.is_err());
+    assert!(missing.is_err());
+    assert!(plugin_still_exists);
+}
+
+#[test]
+fn test_backup_commands_restore_and_prune_backups() {
+    let (project_dir, plugin) = setup_test_plugin("backup-commands-test");
+    let plugin_dir = plugin.get_plugin_dir();
+    let store = BackupStore::new(get_test_config(&project_dir).get_backups_dir());
+
+    // a backup without a usable git repository is never restored
+    let invalid = store.create(&plugin, None).unwrap();
+
+    write(plugin_dir.join("readme.txt"), "Modified readme").unwrap();
+
+    let invalid_restored = backup_restore(get_test_config(&project_dir), "my-plugin", &invalid.id);
+    let readme_after_invalid = read_to_string(plugin_dir.join("readme.txt")).unwrap();
+    let staging_exists = project_dir.join(".wppr-restore-my-plugin").exists();
+
+    remove_dir_all(plugin_dir.join(".git")).unwrap();
+    git(&plugin_dir, &["init", "-q", "."]);
+    git(&plugin_dir, &["config", "user.name", "test person"]);
+    git(&plugin_dir, &["config", "user.email", "testperson@example.com"]);
+    git(&plugin_dir, &["add", "."]);
+    git(&plugin_dir, &["commit", "-q", "-m", "First"]);
+
+    let valid = store.create(&plugin, None).unwrap();
+
+    write(plugin_dir.join("readme.txt"), "Broken upgrade").unwrap();
+
+    let unknown_restored = backup_restore(get_test_config(&project_dir), "my-plugin", "my-plugin-unknown");
+    let restored = backup_restore(get_test_config(&project_dir), "my-plugin", &valid.id);
+    let readme_after_restore = read_to_string(plugin_dir.join("readme.txt")).unwrap();
+
+    let pruned = backup_prune(get_test_config(&project_dir), Some(1));
+    let remaining = store.load_index().unwrap();
+
+    remove_dir_all(&project_dir).unwrap();
+
+    assert!(invalid_restored.is_err());
+    assert_eq!("Modified readme", readme_after_invalid);
+    assert!(!staging_exists);
+
+    assert!(unknown_restored.is_err());
+    assert!(restored.is_ok());
+    assert_eq!("Modified readme", readme_after_restore);
+
+    assert!(pruned.is_ok());
+    assert_eq!(vec![valid], remaining);
+}
@@ -0,0 +1,46 @@
+extern crate wppr;
+
+use std::env;
+use std::fs::{create_dir_all, remove_dir_all, write};
+
+use wppr::changelog::find_version_changelog;
+
+#[test]
+fn test_version_changelogs_are_found_in_plugin_files() {
+    let plugin_dir = env::temp_dir().join(format!("wppr-changelog-test-{}", std::process::id()));
+
+    create_dir_all(&plugin_dir).unwrap();
+
+    // upgrade notices use the same headings as the changelog
+    write(plugin_dir.join("readme.txt"), "=== My Plugin ===\n\
+        Stable tag: 2.1.0\n\
+        \n\
+        == Upgrade Notice ==\n\
+        \n\
+        = 2.1.0 =\n\
+        Upgrade now.\n\
+        \n\
+        == Changelog ==\n\
+        \n\
+        = 2.1.0 - 2024-03-01 =\n\
+        * Add blocks\n\
+        * Fix widgets\n\
+        \n\
+        = 2.0.0 =\n\
+        * Rewrite\n").unwrap();
+
+    let from_readme = find_version_changelog(&plugin_dir, "2.1.0");
+    let missing = find_version_changelog(&plugin_dir, "1.9.0");
+
+    write(plugin_dir.join("CHANGELOG.md"), "# Changelog\n\n## v2.1.0\n\n### Added\n\n- Blocks\n\n## v2.0.0\n\n- Rewrite\n").unwrap();
+
+    let from_changelog = find_version_changelog(&plugin_dir, "2.1.0");
+    let older_from_changelog = find_version_changelog(&plugin_dir, "2.0.0");
+
+    remove_dir_all(&plugin_dir).unwrap();
+
+    assert_eq!(Some("* Add blocks\n* Fix widgets".to_string()), from_readme);
+    assert_eq!(None, missing);
+    assert_eq!(Some("### Added\n\n- Blocks".to_string()), from_changelog);
+    assert_eq!(Some("- Rewrite".to_string()), older_from_changelog);
+}
@@ -21,29 +21,56 @@ fn get_test_config() -> RuntimeConfig {
         binaries: Some(BinariesConfig {
             git: "/bin/true".to_string(),
             wpcli: "/bin/true".to_string(),
+            shell: None,
         }),
         plugins: Some(vec![
             PluginConfig {
                 index_path: get_test_plugin_index(),
                 remote_repository: "/foo/bar.git".to_string(),
                 package_name: "hello/world".to_string(),
-                pre_cmds: None
+                pre_cmds: None,
+                post_cmds: None,
+                cmds_timeout: None,
+                composer: None,
+                composer_strategy: None,
+                hold: None,
+                allow: None,
+                max_version: None
             },
             PluginConfig {
                 index_path: get_test_plugin_index(),
                 remote_repository: "/qwe/asd/zxc.git".to_string(),
                 package_name: "hello2/world2".to_string(),
-                pre_cmds: None
+                pre_cmds: None,
+                post_cmds: None,
+                cmds_timeout: None,
+                composer: None,
+                composer_strategy: None,
+                hold: None,
+                allow: None,
+                max_version: None
             },
         ]),
         git: Some(GitConfig {
             user_email: "".to_string(),
             user_name: "".to_string(),
             force_push: false,
+            backend: None,
+            mirrors_dir: None,
+            commit_message: None,
+            sign: None,
+            signing_key: None,
+            allowed_signers: None,
         }),
+        pre_upgrade: None,
+        post_upgrade: None,
+        repository: None,
+        backups: None,
         verbose: Some(false),
         dry_run: Some(false),
         cwd: Some("".to_string()),
+        file: None,
+        unknown_keys: Vec::new(),
     };
 
     RuntimeConfig::from_toml_config(testcfg).unwrap()
@@ -71,4 +98,185 @@ fn test_plugins_can_be_updated_with_pipelines() {
 
         assert!(pipeline.is_ok());
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_updates_outside_upgrade_policy_are_blocked() {
+    let project_dir = std::env::temp_dir().join(format!("wppr-policy-test-{}", std::process::id()));
+    let plugin_dir = project_dir.join("test-plugin");
+
+    std::fs::create_dir_all(&plugin_dir).unwrap();
+    std::fs::copy(get_test_plugin_index(), plugin_dir.join("plugin.php")).unwrap();
+
+    let mut config = get_test_config();
+    config.binaries.wpcli = testfns::get_tests_dir("data/bin/wp").to_str().unwrap().to_string();
+
+    let plugin_config = PluginConfig {
+        index_path: "test-plugin/plugin.php".to_string(),
+        remote_repository: "/foo/bar.git".to_string(),
+        package_name: "hello/world".to_string(),
+        pre_cmds: None,
+        post_cmds: None,
+        cmds_timeout: None,
+        composer: None,
+        composer_strategy: None,
+        hold: None,
+        allow: Some(wppr::version::VersionBump::Minor),
+        max_version: None
+    };
+
+    let plugin = Plugin::from_config(plugin_config, &project_dir);
+    let backup_dir = project_dir.join(".wpprbackups");
+
+    let outcome = Pipeline::new(&config, &plugin, &backup_dir).unwrap().run();
+    let backup_exists = backup_dir.exists();
+
+    std::fs::remove_dir_all(&project_dir).unwrap();
+
+    match outcome {
+        Ok(UpgradeOutcome::BlockedByPolicy { available, reason }) => {
+            assert_eq!("2.0.0", available);
+            assert!(reason.contains("only minor upgrades are allowed"));
+        }
+        o => panic!("Unexpected outcome {:?}", o)
+    };
+
+    assert!(!backup_exists);
+}
+
+#[test]
+fn test_outdated_plugins_are_listed() {
+    let mut config = get_test_config();
+    config.binaries.wpcli = testfns::get_tests_dir("data/bin/wp").to_str().unwrap().to_string();
+    config.cwd = testfns::get_cwd();
+    config.plugins[1].allow = Some(wppr::version::VersionBump::Minor);
+
+    let outdated = get_outdated_plugins(&config).unwrap();
+
+    assert_eq!(2, outdated.len());
+    assert_eq!("test-plugin", outdated[0].plugin);
+    assert_eq!("hello/world", outdated[0].package_name);
+    assert_eq!("0.1.2", outdated[0].current);
+    assert_eq!(Some("2.0.0".to_string()), outdated[0].available);
+    assert_eq!("upgrade", outdated[0].action);
+    assert!(outdated[1].action.starts_with("blocked by policy"));
+    assert!(outdated[0].is_upgradable());
+    assert!(!outdated[1].is_upgradable());
+}
+
+#[test]
+fn test_outdated_plugins_with_invalid_policy_are_listed() {
+    let mut config = get_test_config();
+    config.binaries.wpcli = testfns::get_tests_dir("data/bin/wp").to_str().unwrap().to_string();
+    config.cwd = testfns::get_cwd();
+    config.plugins[0].max_version = Some("not a version".to_string());
+
+    let outdated = get_outdated_plugins(&config).unwrap();
+
+    assert_eq!(2, outdated.len());
+    assert!(outdated[0].action.starts_with("error: "));
+    assert!(!outdated[0].is_upgradable());
+    assert_eq!("upgrade", outdated[1].action);
+}
+
+#[test]
+fn test_run_summary_is_exposed_as_env_vars() {
+    let summary = RunSummary {
+        upgraded: vec![
+            UpgradedPlugin {
+                plugin: "first/first.php".to_string(),
+                package_name: "hello/first".to_string(),
+                from: "1.0.0".to_string(),
+                to: "1.1.0".to_string(),
+            },
+        ],
+        skipped: vec!["second/second.php".to_string(), "third/third.php".to_string()],
+        failed: Vec::new(),
+    };
+
+    let env_vars = summary.get_env_vars();
+
+    assert_eq!(env_vars[0], ("WPPR_UPGRADED", "first/first.php".to_string()));
+    assert_eq!(env_vars[1], ("WPPR_SKIPPED", "second/second.php,third/third.php".to_string()));
+    assert_eq!(env_vars[2], ("WPPR_FAILED", "".to_string()));
+}
+
+#[test]
+fn test_init_creates_configuration_from_installed_plugins() {
+    let output = testfns::get_tests_dir("data/wpproj/wppr-init-test.toml");
+
+    let options = InitOptions {
+        wpcli: testfns::get_tests_dir("data/bin/wp").to_str().unwrap().to_string(),
+        vendor: "My Agency".to_string(),
+        remote_template: "git@example.com:{vendor}/{slug}.git".to_string(),
+        plugins: Vec::new(),
+        non_wporg_only: true,
+        output: output.clone(),
+        force: true,
+        dry_run: false,
+    };
+
+    let result = init(options);
+    let configuration = TomlConfig::load_from_file(output.clone());
+
+    std::fs::remove_file(&output).unwrap();
+
+    assert!(result.is_ok());
+
+    let plugins = configuration.unwrap().plugins.unwrap();
+
+    assert_eq!(1, plugins.len());
+    assert_eq!("wp-content/plugins/test-plugin/plugin.php", plugins[0].index_path);
+    assert_eq!("my-agency/test-plugin", plugins[0].package_name);
+    assert_eq!("git@example.com:my-agency/test-plugin.git", plugins[0].remote_repository);
+}
+
+#[test]
+fn test_plugins_can_be_added_and_removed() {
+    let config_file = testfns::get_tests_dir("data/wpproj/wppr-add-test.toml");
+    let wpcli = testfns::get_tests_dir("data/bin/wp");
+
+    std::fs::write(&config_file, format!(
+        "# Managed plugins\n\
+         [binaries]\n\
+         git = \"/bin/true\"\n\
+         wpcli = \"{}\"\n\
+         \n\
+         [git]\n\
+         user_name = \"test person\"\n\
+         user_email = \"testperson@example.com\"\n\
+         force_push = false\n\
+         \n\
+         # Keep this one\n\
+         [[plugins]]\n\
+         index_path = \"wp-content/plugins/other-plugin/plugin.php\"\n\
+         remote_repository = \"git@example.com:other.git\"\n\
+         package_name = \"vendor/other\"\n",
+        wpcli.display()
+    )).unwrap();
+
+    let load_config = || {
+        RuntimeConfig::from_toml_config(TomlConfig::load_from_file(config_file.clone()).unwrap()).unwrap()
+    };
+
+    let added = add(load_config(), "test-plugin", "vendor", "git@example.com:{slug}.git");
+    let added_contents = std::fs::read_to_string(&config_file).unwrap();
+    let added_plugins = load_config().plugins;
+
+    let removed = remove(load_config(), "test-plugin", false);
+    let removed_contents = std::fs::read_to_string(&config_file).unwrap();
+
+    std::fs::remove_file(&config_file).unwrap();
+
+    assert!(added.is_ok());
+    assert!(added_contents.contains("# Keep this one"));
+    assert_eq!(2, added_plugins.len());
+    assert_eq!("vendor/other", added_plugins[0].package_name);
+    assert_eq!("vendor/test-plugin", added_plugins[1].package_name);
+    assert_eq!("git@example.com:test-plugin.git", added_plugins[1].remote_repository);
+
+    assert!(removed.is_ok());
+    assert!(removed_contents.starts_with("# Managed plugins\n"));
+    assert!(removed_contents.contains("# Keep this one"));
+    assert!(!removed_contents.contains("test-plugin"));
+}
@@ -0,0 +1,155 @@
+extern crate serde_json;
+extern crate wppr;
+
+use std::path::PathBuf;
+use wppr::composer::*;
+use wppr::config::{ComposerStrategy, PluginConfig};
+use wppr::wordpress::Plugin;
+
+#[path = "./testfns.rs"]
+mod testfns;
+
+fn get_test_plugin() -> Plugin {
+    let pluginconfig = PluginConfig {
+        index_path: testfns::get_tests_dir("data/plugins/header-plugin/header-plugin.php")
+            .to_str()
+            .unwrap()
+            .to_string(),
+        package_name: "my-agency/header-plugin".to_string(),
+        remote_repository: "".to_string(),
+        pre_cmds: None,
+        post_cmds: None,
+        cmds_timeout: None,
+        composer: None,
+        composer_strategy: None,
+        hold: None,
+        allow: None,
+        max_version: None
+    };
+
+    Plugin::from_config(pluginconfig, &PathBuf::from(""))
+}
+
+#[test]
+fn test_composerjson_is_generated_from_plugin_header() {
+    let plugin = get_test_plugin();
+    let composerjson = generate_composerjson(&plugin, plugin.header.as_ref().unwrap());
+
+    assert_eq!("my-agency/header-plugin", composerjson["name"]);
+    assert_eq!("Plugin with a complete header.", composerjson["description"]);
+    assert_eq!("wordpress-plugin", composerjson["type"]);
+    assert_eq!("GPL-2.0-or-later", composerjson["license"]);
+    assert_eq!("https://example.com/header-plugin", composerjson["homepage"]);
+    assert_eq!("Jane Doe", composerjson["authors"][0]["name"]);
+    assert_eq!("https://example.com", composerjson["authors"][0]["homepage"]);
+    assert_eq!(">=7.2", composerjson["require"]["php"]);
+    assert!(composerjson["require"]["composer/installers"].is_string());
+    assert_eq!("header-plugin", composerjson["extra"]["installer-name"]);
+    assert_eq!("2.7.1.3", composerjson["extra"]["wppr"]["version"]);
+    assert_eq!("5.2", composerjson["extra"]["wppr"]["requires-wp"]);
+    assert_eq!(true, composerjson["extra"]["wppr"]["network"]);
+}
+
+#[test]
+fn test_generated_composerjson_is_valid_json() {
+    let plugin = get_test_plugin();
+    let contents = to_json_string(&generate_composerjson(&plugin, plugin.header.as_ref().unwrap()));
+
+    let parsed: serde_json::Value = serde_json::from_str(&contents).unwrap();
+
+    assert_eq!("my-agency/header-plugin", parsed["name"]);
+    assert!(contents.starts_with("{\n    \"name\": \"my-agency/header-plugin\",\n    \"description\""));
+    assert!(is_generated_composerjson(&contents, "my-agency/header-plugin"));
+}
+
+#[test]
+fn test_vendor_composerjson_is_not_considered_generated() {
+    let vendor = "{\"name\": \"vendor/plugin\", \"type\": \"wordpress-plugin\"}";
+    let legacy = "{\"name\": \"my-agency/plugin\"\"type\": \"wordpress-plugin\"}";
+
+    assert!(!is_generated_composerjson(vendor, "vendor/plugin"));
+    assert!(!is_generated_composerjson("not json", "vendor/plugin"));
+    assert!(is_generated_composerjson(legacy, "my-agency/plugin"));
+}
+
+fn get_test_plugin_with_strategy(strategy: ComposerStrategy) -> Plugin {
+    let mut plugin = get_test_plugin();
+
+    plugin.composer_strategy = strategy;
+    plugin.composer_overrides = Some(serde_json::json!({
+        "require": { "vendor/library": "^2.0" },
+        "extra": { "installer-name": "custom-name" }
+    }));
+
+    plugin
+}
+
+const VENDOR_COMPOSERJSON: &str = r#"{
+    "name": "vendor/header-plugin",
+    "require": { "php": ">=5.6", "vendor/sdk": "^1.0" },
+    "autoload": { "psr-4": { "Vendor\\": "src/" } }
+}"#;
+
+#[test]
+fn test_vendor_composerjson_is_merged() {
+    let plugin = get_test_plugin_with_strategy(ComposerStrategy::Merge);
+    let header = plugin.header.clone().unwrap();
+
+    let composerjson = get_plugin_composerjson(&plugin, &header, Some(VENDOR_COMPOSERJSON))
+        .unwrap()
+        .unwrap();
+
+    assert_eq!("my-agency/header-plugin", composerjson["name"]);
+    assert_eq!(">=7.2", composerjson["require"]["php"]);
+    assert_eq!("^1.0", composerjson["require"]["vendor/sdk"]);
+    assert_eq!("^2.0", composerjson["require"]["vendor/library"]);
+    assert_eq!("src/", composerjson["autoload"]["psr-4"]["Vendor\\"]);
+    assert_eq!("custom-name", composerjson["extra"]["installer-name"]);
+    assert_eq!("2.7.1.3", composerjson["extra"]["wppr"]["version"]);
+}
+
+#[test]
+fn test_vendor_composerjson_is_overwritten() {
+    let plugin = get_test_plugin_with_strategy(ComposerStrategy::Overwrite);
+    let header = plugin.header.clone().unwrap();
+
+    let composerjson = get_plugin_composerjson(&plugin, &header, Some(VENDOR_COMPOSERJSON))
+        .unwrap()
+        .unwrap();
+
+    assert!(composerjson.get("autoload").is_none());
+    assert!(composerjson["require"].get("vendor/sdk").is_none());
+    assert_eq!("^2.0", composerjson["require"]["vendor/library"]);
+}
+
+#[test]
+fn test_vendor_composerjson_is_kept() {
+    let plugin = get_test_plugin_with_strategy(ComposerStrategy::Keep);
+    let header = plugin.header.clone().unwrap();
+
+    let vendor_result = get_plugin_composerjson(&plugin, &header, Some(VENDOR_COMPOSERJSON)).unwrap();
+    let missing_result = get_plugin_composerjson(&plugin, &header, None).unwrap();
+
+    assert!(vendor_result.is_none());
+    assert_eq!("custom-name", missing_result.unwrap()["extra"]["installer-name"]);
+}
+
+#[test]
+fn test_generated_composerjson_is_regenerated_with_overrides() {
+    let plugin = get_test_plugin_with_strategy(ComposerStrategy::Keep);
+    let header = plugin.header.clone().unwrap();
+    let generated = to_json_string(&generate_composerjson(&get_test_plugin(), &header));
+
+    let generated_result = get_plugin_composerjson(&plugin, &header, Some(&generated)).unwrap().unwrap();
+
+    assert_eq!("custom-name", generated_result["extra"]["installer-name"]);
+    assert_eq!("my-agency/header-plugin", generated_result["name"]);
+}
+
+#[test]
+fn test_invalid_vendor_composerjson_cannot_be_merged() {
+    let plugin = get_test_plugin_with_strategy(ComposerStrategy::Merge);
+    let header = plugin.header.clone().unwrap();
+
+    assert!(get_plugin_composerjson(&plugin, &header, Some("{ invalid")).is_err());
+}
@@ -12,6 +12,8 @@ use std::path::PathBuf;
 use std::fs::File;
 use std::io::Read;
 use wppr::config::*;
+use wppr::version::VersionBump;
+use wppr::Error;
 
 #[test]
 fn test_configuration_is_loaded_from_toml() {
@@ -46,10 +48,34 @@ fn test_configuration_file_is_loaded_properly() {
         configuration.binaries.unwrap().git,
         "/usr/bin/my-git".to_string()
     );
+    let git = configuration.git.unwrap();
+
+    assert_eq!(git.backend, Some(GitBackend::Libgit2));
+    assert_eq!(git.get_commit_message(), "Release {plugin} {new_version}".to_string());
+    assert_eq!(git.sign, Some(SigningFormat::Ssh));
+    assert_eq!(git.signing_key, Some("/home/wppr/.ssh/id_ed25519".to_string()));
     assert_eq!(
-        configuration.plugins.unwrap()[0].package_name,
-        "test/package".to_string()
+        configuration.post_upgrade.unwrap().timeout,
+        Some(60)
     );
+
+    let plugins = configuration.plugins.unwrap();
+
+    assert_eq!(plugins[0].package_name, "test/package".to_string());
+    assert_eq!(plugins[1].post_cmds, Some(vec!["npm run build".to_string()]));
+    assert_eq!(plugins[1].cmds_timeout, Some(600));
+    assert_eq!(plugins[0].composer_strategy, None);
+    assert_eq!(plugins[1].composer_strategy, Some(ComposerStrategy::Merge));
+
+    assert_eq!(plugins[0].hold, Some(PluginHold::Enabled(true)));
+    assert_eq!(plugins[1].hold, None);
+    assert_eq!(plugins[1].allow, Some(VersionBump::Minor));
+    assert_eq!(plugins[1].max_version, Some("5.9".to_string()));
+
+    let composer = plugins[1].composer.clone().unwrap();
+
+    assert_eq!(composer["require"]["php"].as_str(), Some(">=8.0"));
+    assert_eq!(composer["replace"]["vendor/original"].as_str(), Some("*"));
 }
 
 #[test]
@@ -58,36 +84,179 @@ fn test_configuration_is_flattened_to_runtime_config() {
         binaries: Some(BinariesConfig {
             git: "/bin/true".to_string(),
             wpcli: "/bin/true".to_string(),
+            shell: None,
         }),
         git: Some(GitConfig {
             user_name: "wppr".to_string(),
             user_email: "wppe@wppr.wppr".to_string(),
             force_push: false,
+            backend: None,
+            mirrors_dir: None,
+            commit_message: None,
+            sign: None,
+            signing_key: None,
+            allowed_signers: None,
         }),
         plugins: Some(vec![
             PluginConfig {
                 package_name: "hello/world-package".to_string(),
                 index_path: "pkgs/hello/world.php".to_string(),
                 remote_repository: "../hello-world.git".to_string(),
-                pre_cmds: Some(Vec::new())
+                pre_cmds: Some(Vec::new()),
+                post_cmds: None,
+                cmds_timeout: None,
+                composer: None,
+                composer_strategy: None,
+                hold: None,
+                allow: None,
+                max_version: None
             },
             PluginConfig {
                 package_name: "foo/bar-package".to_string(),
                 index_path: "pkgs/foo/bar.php".to_string(),
                 remote_repository: "../bar.git".to_string(),
-                pre_cmds: Some(Vec::new())
+                pre_cmds: Some(Vec::new()),
+                post_cmds: None,
+                cmds_timeout: None,
+                composer: None,
+                composer_strategy: None,
+                hold: None,
+                allow: None,
+                max_version: None
             },
         ]),
+        pre_upgrade: Some(HooksConfig {
+            commands: vec!["echo 'before'".to_string()],
+            timeout: None,
+        }),
+        post_upgrade: None,
+        repository: None,
+        backups: None,
         verbose: Some(false),
         dry_run: Some(true),
         cwd: Some("/my/cwd/path".to_string()),
+        file: None,
+        unknown_keys: Vec::new(),
     };
 
     let runtimecfg = RuntimeConfig::from_toml_config(tomlcfg).unwrap();
 
     assert_eq!(runtimecfg.cwd, PathBuf::from("/my/cwd/path"));
+    assert_eq!(runtimecfg.pre_upgrade.commands.len(), 1);
+    assert!(runtimecfg.post_upgrade.commands.is_empty());
     assert_eq!(
         runtimecfg.plugins[1].package_name,
         "foo/bar-package".to_string()
     );
 }
+
+#[test]
+fn test_configuration_without_git_section_is_an_error() {
+    let tomlcfg: TomlConfig = TomlConfig {
+        binaries: Some(BinariesConfig {
+            git: "/bin/true".to_string(),
+            wpcli: "/bin/true".to_string(),
+            shell: None,
+        }),
+        git: None,
+        plugins: None,
+        pre_upgrade: None,
+        post_upgrade: None,
+        repository: None,
+        backups: None,
+        verbose: None,
+        dry_run: None,
+        cwd: Some("/my/cwd/path".to_string()),
+        file: None,
+        unknown_keys: Vec::new(),
+    };
+
+    match RuntimeConfig::from_toml_config(tomlcfg) {
+        Err(Error::Config(e)) => assert_eq!(e.key, Some("git".to_string())),
+        _ => panic!("Expected a configuration error"),
+    };
+}
+
+#[test]
+fn test_configuration_syntax_errors_are_reported_with_position() {
+    let src_toml = testfns::get_tests_dir("data/invalid-syntax.toml");
+
+    match TomlConfig::load_from_file(src_toml.clone()) {
+        Err(Error::Config(e)) => {
+            assert_eq!(e.file, Some(src_toml));
+            assert_eq!(e.line, Some(3));
+            assert!(e.column.is_some());
+        }
+        _ => panic!("Expected a configuration error"),
+    };
+}
+
+#[test]
+fn test_configuration_type_errors_are_reported_with_key() {
+    let src_toml = testfns::get_tests_dir("data/invalid-type.toml");
+
+    match TomlConfig::load_from_file(src_toml) {
+        Err(Error::Config(e)) => {
+            assert_eq!(e.key, Some("git.force_push".to_string()));
+            assert_eq!(e.line, Some(8));
+            assert_eq!(e.column, Some(1));
+        }
+        _ => panic!("Expected a configuration error"),
+    };
+}
+
+#[test]
+fn test_unknown_configuration_keys_are_collected() {
+    let src_toml = testfns::get_tests_dir("data/unknown-keys.toml");
+
+    let configuration = TomlConfig::load_from_file(src_toml).unwrap();
+
+    assert_eq!(
+        configuration.unknown_keys,
+        vec!["binaries.php".to_string(), "plugins[0].pre_cmd".to_string()]
+    );
+}
+
+#[test]
+fn test_configuration_file_is_found_from_parent_directories() {
+    let plugin_dir = testfns::get_tests_dir("data/wpproj/public/wp-content/plugins/test-plugin");
+
+    assert_eq!(
+        find_config_file(&plugin_dir),
+        Some(testfns::get_tests_dir("data/wpproj/wppr.toml"))
+    );
+    assert_eq!(
+        resolve_config_file(None, &plugin_dir).unwrap(),
+        testfns::get_tests_dir("data/wpproj/wppr.toml")
+    );
+    assert_eq!(
+        resolve_config_file(Some("../valid.toml".to_string()), &testfns::get_tests_dir("data/wpproj")).unwrap(),
+        testfns::get_tests_dir("data/wpproj/../valid.toml")
+    );
+}
+
+#[test]
+fn test_plugin_index_paths_are_resolved_from_wordpress_root() {
+    let src_toml = testfns::get_tests_dir("data/wpproj/wppr.toml");
+    let runtimecfg = RuntimeConfig::from_toml_config(TomlConfig::load_from_file(src_toml).unwrap()).unwrap();
+
+    assert_eq!(runtimecfg.cwd, testfns::get_tests_dir("data/wpproj"));
+    assert_eq!(runtimecfg.wp_root, Some(testfns::get_tests_dir("data/wpproj/public")));
+    assert_eq!(
+        runtimecfg.get_index_path_base(&runtimecfg.plugins[0]),
+        testfns::get_tests_dir("data/wpproj/public")
+    );
+    assert_eq!(find_wordpress_root(&testfns::get_tests_dir("data/plugins")), None);
+}
+
+#[test]
+fn test_basic_auth_password_is_not_printed() {
+    let auth = BasicAuthConfig {
+        username: "composer".to_string(),
+        password: "s3cret".to_string(),
+    };
+    let printed = format!("{:?}", auth);
+
+    assert!(printed.contains("composer"));
+    assert!(!printed.contains("s3cret"));
+}
@@ -0,0 +1,17 @@
+<?php
+/**
+ * Plugin Name:       Header Plugin
+ * Plugin URI:        https://example.com/header-plugin
+ * Description:       Plugin with a complete header.
+ * Version:           2.7.1.3
+ * Requires at least: 5.2
+ * Requires PHP:      7.2
+ * Author:            Jane Doe
+ * Author URI:        https://example.com
+ * License:           GPL v2 or later
+ * License URI:       https://www.gnu.org/licenses/gpl-2.0.html
+ * Text Domain:       header-plugin
+ * Network:           true
+ * Update URI:        https://example.com/updates */
+ * Requires Plugins:  woocommerce, akismet
+ */
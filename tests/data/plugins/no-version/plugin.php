@@ -0,0 +1,5 @@
+<?php
+
+/**
+ * Plugin Name: No version here
+ */
@@ -0,0 +1,3 @@
+<?php
+
+// Test WordPress configuration for wppr.
@@ -0,0 +1,7 @@
+<?php
+
+/**
+ * Plugin name: This is a test plugin
+ * Version: 0.1.2
+ * Description: Hello world
+ */
\ No newline at end of file
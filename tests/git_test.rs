@@ -0,0 +1,382 @@
+extern crate wppr;
+
+use std::fs::{create_dir_all, read_to_string, remove_dir_all, write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use wppr::commands::get_tag_verifications;
+use wppr::config::{GitBackend, GitConfig, SigningFormat};
+use wppr::git::{get_backend, get_change_summary, FileChange, FileStatus, VcsBackend};
+
+#[path = "./testfns.rs"]
+mod testfns;
+
+use testfns::{get_test_config, get_test_dir, get_test_plugin, git, write_test_config};
+
+fn get_test_backend(backend: GitBackend, dir: &Path) -> Box<dyn VcsBackend> {
+    get_backend("git".to_string(), get_test_git_config(backend), dir.to_path_buf())
+}
+
+fn get_test_git_config(backend: GitBackend) -> GitConfig {
+    GitConfig {
+        user_name: "test person".to_string(),
+        user_email: "testperson@example.com".to_string(),
+        force_push: false,
+        backend: Some(backend),
+        mirrors_dir: None,
+        commit_message: None,
+        sign: None,
+        signing_key: None,
+        allowed_signers: None,
+    }
+}
+
+/// Generate an SSH signing key, returning the private key path and the public
+/// key.
+fn generate_ssh_key(dir: &Path, name: &str) -> (PathBuf, String) {
+    let key = dir.join(name);
+    let output = Command::new("ssh-keygen")
+        .args(["-q", "-t", "ed25519", "-N", "", "-C", name, "-f"])
+        .arg(&key)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+
+    let public_key = read_to_string(key.with_extension("pub")).unwrap();
+
+    (key, public_key.trim().to_string())
+}
+
+fn get_signing_backend(backend: GitBackend, dir: &Path, key: &Path) -> Box<dyn VcsBackend> {
+    let mut config = get_test_git_config(backend);
+
+    config.sign = Some(SigningFormat::Ssh);
+    config.signing_key = Some(key.to_string_lossy().to_string());
+
+    get_backend("git".to_string(), config, dir.to_path_buf())
+}
+
+fn assert_backend_manages_repository(backend: GitBackend) {
+    let test_dir = get_test_dir(&format!("git-{}-test", backend.as_str()));
+    let plugin_dir = test_dir.join("my-plugin");
+    let remote_dir = test_dir.join("remote.git");
+
+    create_dir_all(&plugin_dir).unwrap();
+    create_dir_all(&remote_dir).unwrap();
+    git(&remote_dir, &["init", "-q", "--bare", "."]);
+
+    let git_backend = get_test_backend(backend, &plugin_dir);
+
+    let initialized_before = git_backend.repository_is_initialized().unwrap();
+
+    git_backend.initialize_repository().unwrap();
+    git(&plugin_dir, &["symbolic-ref", "HEAD", "refs/heads/master"]);
+
+    write(plugin_dir.join("my-plugin.php"), "<?php\n// Version: 1.0.0\n").unwrap();
+    create_dir_all(plugin_dir.join("assets/js")).unwrap();
+    write(plugin_dir.join("assets/js/app.js"), "// app\n").unwrap();
+
+    let initialized = git_backend.repository_is_initialized().unwrap();
+    let initialized_in_git_dir = git_backend.with_working_directory(plugin_dir.join(".git")).repository_is_initialized().unwrap();
+    let changed = git_backend.has_uncommited_changes().unwrap();
+    let initial_status = git_backend.get_status().unwrap();
+    let committed = git_backend.add_and_commit_changes("Initial commit").unwrap();
+    let changed_after_commit = git_backend.has_uncommited_changes().unwrap();
+    let committed_again = git_backend.add_and_commit_changes("Initial commit").unwrap();
+    let author = git(&plugin_dir, &["log", "-1", "--format=%an <%ae>"]);
+
+    git_backend.add_tag("1.0.0".to_string(), "my-plugin 1.0.0").unwrap();
+
+    let tag_exists = git_backend.tag_exists("1.0.0").unwrap();
+    let missing_tag_exists = git_backend.tag_exists("2.0.0").unwrap();
+    let hash = git_backend.get_commit_hash("1.0.0").unwrap();
+    let time = git_backend.get_commit_time("1.0.0").unwrap();
+    let file = git_backend.get_file_at("1.0.0", "assets/js/app.js").unwrap();
+    let missing_file = git_backend.get_file_at("1.0.0", "missing.php").unwrap();
+
+    // upgrades remove files too
+    write(plugin_dir.join("my-plugin.php"), "<?php\n// Version: 1.1.0\n").unwrap();
+    remove_dir_all(plugin_dir.join("assets")).unwrap();
+
+    let upgrade_status = git_backend.get_status().unwrap();
+
+    git_backend.add_and_commit_changes(&format!("Update\n\n{}", get_change_summary(&upgrade_status))).unwrap();
+    git_backend.add_tag("1.1.0".to_string(), "my-plugin 1.1.0").unwrap();
+
+    let upgrade_body = git(&plugin_dir, &["log", "-1", "--format=%b"]);
+
+    let tags = git_backend.list_tags().unwrap();
+    let tracked_files = git(&plugin_dir, &["ls-files"]);
+
+    // reset discards uncommitted changes
+    write(plugin_dir.join("my-plugin.php"), "<?php\n// broken\n").unwrap();
+    git_backend.reset_contents().unwrap();
+
+    let changed_after_reset = git_backend.has_uncommited_changes().unwrap();
+
+    // checking out an old tree stages the old contents on the current branch
+    git_backend.checkout_tree("1.0.0").unwrap();
+
+    let staged = git_backend.has_staged_changes().unwrap();
+
+    git_backend.commit_staged("Roll back to 1.0.0").unwrap();
+
+    let staged_after_commit = git_backend.has_staged_changes().unwrap();
+    let restored_file_exists = plugin_dir.join("assets/js/app.js").exists();
+    let rollback_message = git(&plugin_dir, &["log", "-1", "--format=%s"]);
+
+    git_backend.add_remote(remote_dir.to_string_lossy().to_string()).unwrap();
+
+    let remote_added_again = git_backend.add_remote(remote_dir.to_string_lossy().to_string()).unwrap();
+
+    git_backend.push_to_remote().unwrap();
+
+    let remote_head = git(&remote_dir, &["rev-parse", "refs/heads/master"]);
+    let remote_tags = git(&remote_dir, &["tag", "--list"]);
+    let tag_type = git(&plugin_dir, &["cat-file", "-t", "1.1.0"]);
+    let tag_message = git(&plugin_dir, &["for-each-ref", "refs/tags/1.1.0", "--format=%(contents)"]);
+    let local_head = git(&plugin_dir, &["rev-parse", "HEAD"]);
+    let expected_time = git(&plugin_dir, &["log", "-1", "--format=%cI", "1.0.0"]);
+    let expected_hash = git(&plugin_dir, &["rev-parse", "1.0.0^{commit}"]);
+
+    git_backend.add_tag("2.0.0".to_string(), "my-plugin 2.0.0").unwrap();
+    git_backend.delete_tag("2.0.0").unwrap();
+
+    let deleted_tag_exists = git_backend.tag_exists("2.0.0").unwrap();
+
+    git_backend.reset_to("1.0.0").unwrap();
+
+    let reset_head = git(&plugin_dir, &["rev-parse", "HEAD"]);
+
+    remove_dir_all(&test_dir).unwrap();
+
+    assert!(!initialized_before);
+    assert!(initialized);
+    assert!(!initialized_in_git_dir);
+    assert!(changed);
+    assert_eq!(
+        vec![
+            FileChange { path: "assets/js/app.js".to_string(), status: FileStatus::Untracked },
+            FileChange { path: "my-plugin.php".to_string(), status: FileStatus::Untracked },
+        ],
+        initial_status
+    );
+    assert!(committed);
+    assert!(!changed_after_commit);
+    assert!(!committed_again);
+    assert_eq!("test person <testperson@example.com>", author);
+
+    assert!(tag_exists);
+    assert!(!missing_tag_exists);
+    assert_eq!(expected_hash, hash);
+    assert_eq!(expected_time, time);
+    assert_eq!(Some("// app\n".to_string()), file);
+    assert_eq!(None, missing_file);
+
+    assert_eq!(
+        vec![
+            FileChange { path: "assets/js/app.js".to_string(), status: FileStatus::Deleted },
+            FileChange { path: "my-plugin.php".to_string(), status: FileStatus::Modified },
+        ],
+        upgrade_status
+    );
+    assert_eq!(
+        "2 files changed: 0 added, 1 modified, 1 deleted\n\ndeleted: assets/js/app.js\nmodified: my-plugin.php",
+        upgrade_body
+    );
+    assert_eq!(vec!["1.0.0".to_string(), "1.1.0".to_string()], tags);
+    assert_eq!("my-plugin.php", tracked_files);
+    assert!(!changed_after_reset);
+
+    assert!(staged);
+    assert!(!staged_after_commit);
+    assert!(restored_file_exists);
+    assert_eq!("Roll back to 1.0.0", rollback_message);
+
+    assert!(remote_added_again);
+    assert_eq!(local_head, remote_head);
+    assert_eq!("1.0.0\n1.1.0", remote_tags);
+    assert_eq!("tag", tag_type);
+    assert_eq!("my-plugin 1.1.0", tag_message);
+
+    assert!(!deleted_tag_exists);
+    assert_eq!(expected_hash, reset_head);
+}
+
+#[test]
+fn test_cli_backend_manages_repository() {
+    assert_backend_manages_repository(GitBackend::Cli);
+}
+
+#[test]
+fn test_libgit2_backend_manages_repository() {
+    assert_backend_manages_repository(GitBackend::Libgit2);
+}
+
+fn assert_backend_ignores_parent_repository(backend: GitBackend) {
+    let site_dir = get_test_dir(&format!("git-{}-nested-test", backend.as_str()));
+    let plugin_dir = site_dir.join("wp-content/plugins/my-plugin");
+
+    create_dir_all(&plugin_dir).unwrap();
+    git(&site_dir, &["init", "-q", "."]);
+
+    let git_backend = get_test_backend(backend, &plugin_dir);
+
+    // a plugin inside a site repository has no repository of its own yet
+    let initialized_in_site = git_backend.repository_is_initialized().unwrap();
+
+    git_backend.initialize_repository().unwrap();
+
+    let initialized = git_backend.repository_is_initialized().unwrap();
+    let site_initialized = git_backend.with_working_directory(site_dir.join("wp-content")).repository_is_initialized().unwrap();
+
+    remove_dir_all(&site_dir).unwrap();
+
+    assert!(!initialized_in_site);
+    assert!(initialized);
+    assert!(!site_initialized);
+}
+
+#[test]
+fn test_cli_backend_ignores_parent_repository() {
+    assert_backend_ignores_parent_repository(GitBackend::Cli);
+}
+
+#[test]
+fn test_libgit2_backend_ignores_parent_repository() {
+    assert_backend_ignores_parent_repository(GitBackend::Libgit2);
+}
+
+#[test]
+fn test_libgit2_backend_pushes_tags() {
+    let test_dir = get_test_dir("git-libgit2-tags-test");
+    let plugin_dir = test_dir.join("my-plugin");
+    let remote_dir = test_dir.join("remote.git");
+
+    create_dir_all(&plugin_dir).unwrap();
+    create_dir_all(&remote_dir).unwrap();
+    git(&remote_dir, &["init", "-q", "--bare", "."]);
+
+    let git_backend = get_test_backend(GitBackend::Libgit2, &plugin_dir);
+
+    git_backend.initialize_repository().unwrap();
+    write(plugin_dir.join("my-plugin.php"), "<?php\n").unwrap();
+    git_backend.add_and_commit_changes("Initial commit").unwrap();
+    git_backend.add_tag("1.0.0".to_string(), "my-plugin 1.0.0").unwrap();
+    git_backend.add_remote(remote_dir.to_string_lossy().to_string()).unwrap();
+    git_backend.push_to_remote().unwrap();
+
+    let branch = git(&plugin_dir, &["symbolic-ref", "HEAD"]);
+    let remote_tags = git(&remote_dir, &["tag", "--list"]);
+
+    remove_dir_all(&test_dir).unwrap();
+
+    assert_eq!("refs/heads/master", branch);
+    assert_eq!("1.0.0", remote_tags);
+}
+
+fn assert_backend_signs_commits_and_tags(backend: GitBackend) {
+    let test_dir = get_test_dir(&format!("git-{}-signing-test", backend.as_str()));
+    let plugin_dir = test_dir.join("my-plugin");
+    let allowed_signers = test_dir.join("allowed_signers");
+
+    create_dir_all(&plugin_dir).unwrap();
+
+    let (key, public_key) = generate_ssh_key(&test_dir, "trusted");
+    let (other_key, _) = generate_ssh_key(&test_dir, "untrusted");
+
+    write(&allowed_signers, format!("testperson@example.com {}\n", public_key)).unwrap();
+
+    let git_backend = get_signing_backend(backend, &plugin_dir, &key);
+
+    git_backend.initialize_repository().unwrap();
+    write(plugin_dir.join("my-plugin.php"), "<?php\n").unwrap();
+    git_backend.add_and_commit_changes("Initial commit").unwrap();
+    git_backend.add_tag("1.0.0".to_string(), "my-plugin 1.0.0").unwrap();
+
+    write(plugin_dir.join("my-plugin.php"), "<?php\n// staged\n").unwrap();
+    git(&plugin_dir, &["add", "."]);
+    git_backend.commit_staged("Staged commit").unwrap();
+
+    let commits = git(&plugin_dir, &["log", "--format=%H"]);
+    let unsigned_commits: Vec<&str> = commits
+        .lines()
+        .filter(|c| !git(&plugin_dir, &["cat-file", "commit", c]).contains("-----BEGIN SSH SIGNATURE-----"))
+        .collect();
+    let unsigned_commits = unsigned_commits.len();
+
+    git(&plugin_dir, &["tag", "-a", "1.0.1", "-m", "unsigned"]);
+    git(&plugin_dir, &[
+        "-c",
+        "gpg.format=ssh",
+        "-c",
+        &format!("user.signingkey={}", other_key.display()),
+        "tag",
+        "-s",
+        "1.0.2",
+        "-m",
+        "untrusted"
+    ]);
+
+    let trusted = git_backend.verify_tag("1.0.0", &allowed_signers).unwrap();
+    let unsigned = git_backend.verify_tag("1.0.1", &allowed_signers).unwrap();
+    let untrusted = git_backend.verify_tag("1.0.2", &allowed_signers).unwrap();
+    let tag_message = git(&plugin_dir, &["for-each-ref", "refs/tags/1.0.0", "--format=%(contents:subject)"]);
+
+    remove_dir_all(&test_dir).unwrap();
+
+    assert_eq!(0, unsigned_commits);
+    assert_eq!("my-plugin 1.0.0", tag_message);
+    assert_eq!(None, trusted);
+    assert_eq!(Some("no signature found".to_string()), unsigned);
+    assert_eq!(Some("No principal matched.".to_string()), untrusted);
+}
+
+#[test]
+fn test_cli_backend_signs_commits_and_tags() {
+    assert_backend_signs_commits_and_tags(GitBackend::Cli);
+}
+
+#[test]
+fn test_libgit2_backend_signs_commits_and_tags() {
+    assert_backend_signs_commits_and_tags(GitBackend::Libgit2);
+}
+
+#[test]
+fn test_plugin_repository_tags_are_verified() {
+    let project_dir = get_test_dir("git-verify-test");
+    let plugin_dir = project_dir.join("test-plugin");
+
+    create_dir_all(&plugin_dir).unwrap();
+
+    let (key, public_key) = generate_ssh_key(&project_dir, "trusted");
+
+    write(project_dir.join("allowed_signers"), format!("testperson@example.com {}\n", public_key)).unwrap();
+    write(plugin_dir.join("plugin.php"), "<?php\n/**\n * Plugin Name: Test Plugin\n * Version: 0.1.2\n */\n").unwrap();
+
+    let git_backend = get_signing_backend(GitBackend::Cli, &plugin_dir, &key);
+
+    git_backend.initialize_repository().unwrap();
+    git_backend.add_and_commit_changes("Initial commit").unwrap();
+    git_backend.add_tag("0.1.2".to_string(), "test-plugin 0.1.2").unwrap();
+    git(&plugin_dir, &["tag", "-a", "0.1.1", "-m", "unsigned"]);
+
+    let load_config = |allowed_signers: &str| {
+        write_test_config(&project_dir, allowed_signers, &get_test_plugin("git@example.com:test-plugin.git"));
+
+        get_test_config(&project_dir)
+    };
+
+    let unconfigured = get_tag_verifications(&load_config(""));
+    let verifications = get_tag_verifications(&load_config("allowed_signers = \"allowed_signers\"\n")).unwrap();
+
+    remove_dir_all(&project_dir).unwrap();
+
+    assert!(unconfigured.unwrap_err().to_string().contains("git.allowed_signers"));
+    assert_eq!(2, verifications.len());
+    assert_eq!("0.1.1", verifications[0].tag);
+    assert_eq!(Some("no signature found".to_string()), verifications[0].error);
+    assert_eq!("0.1.2", verifications[1].tag);
+    assert_eq!(None, verifications[1].error);
+}
@@ -34,6 +34,6 @@ fn test_app_config_works() {
     assert_cli::Assert::command(&[bin, "--configuration", "./relative/path.toml", "list"])
         .fails()
         .stderr()
-        .contains("given as an absolute path")
+        .contains("configuration file exists")
         .unwrap();
 }
@@ -0,0 +1,183 @@
+extern crate wppr;
+
+use std::fs::{create_dir_all, read_link, read_to_string, remove_dir_all, write};
+use std::os::unix::fs::symlink;
+use std::path::PathBuf;
+
+use wppr::commands::{get_managed_plugins, rollback};
+use wppr::mirror::*;
+use wppr::pipeline::{Pipeline, UpgradeOutcome};
+
+#[path = "./testfns.rs"]
+mod testfns;
+
+use testfns::{get_test_config, get_test_dir, get_test_plugin, git, write_test_config};
+
+#[test]
+fn test_directories_are_synced() {
+    let test_dir = get_test_dir("mirror-sync-test");
+    let src = test_dir.join("src");
+    let dest = test_dir.join("dest");
+
+    create_dir_all(src.join("assets/js")).unwrap();
+    create_dir_all(src.join("lang")).unwrap();
+    create_dir_all(dest.join(".git")).unwrap();
+    create_dir_all(dest.join("assets/css")).unwrap();
+
+    write(src.join("plugin.php"), "<?php\n// new\n").unwrap();
+    write(src.join("assets/js/app.js"), "// app\n").unwrap();
+    symlink("plugin.php", src.join("index.php")).unwrap();
+
+    write(dest.join("plugin.php"), "<?php\n// old\n").unwrap();
+    write(dest.join("assets/css/old.css"), "").unwrap();
+    write(dest.join("removed.php"), "").unwrap();
+    write(dest.join("lang"), "a file where the source has a directory").unwrap();
+    write(dest.join(".git/HEAD"), "ref: refs/heads/master\n").unwrap();
+    write(dest.join("composer.json"), "{}").unwrap();
+
+    let synced = sync_directory(&src, &dest, &MIRROR_FILES);
+
+    let plugin = read_to_string(dest.join("plugin.php")).unwrap();
+    let app = read_to_string(dest.join("assets/js/app.js")).unwrap();
+    let link = read_link(dest.join("index.php")).unwrap();
+    let lang_is_dir = dest.join("lang").is_dir();
+    let old_css_exists = dest.join("assets/css").exists();
+    let removed_exists = dest.join("removed.php").exists();
+    let git_head = read_to_string(dest.join(".git/HEAD")).unwrap();
+    let composerjson = read_to_string(dest.join("composer.json")).unwrap();
+
+    remove_dir_all(&test_dir).unwrap();
+
+    assert!(synced.is_ok());
+    assert_eq!("<?php\n// new\n", plugin);
+    assert_eq!("// app\n", app);
+    assert_eq!(PathBuf::from("plugin.php"), link);
+    assert!(lang_is_dir);
+    assert!(!old_css_exists);
+    assert!(!removed_exists);
+    assert_eq!("ref: refs/heads/master\n", git_head);
+    assert_eq!("{}", composerjson);
+}
+
+#[test]
+fn test_mirrored_plugins_are_upgraded_outside_plugin_directory() {
+    let project_dir = get_test_dir("mirror-pipeline-test");
+    let plugin_dir = project_dir.join("test-plugin");
+    let mirror_dir = project_dir.join("mirrors/test-plugin");
+    let remote_dir = project_dir.join("remote.git");
+
+    create_dir_all(&plugin_dir).unwrap();
+    create_dir_all(&remote_dir).unwrap();
+    git(&remote_dir, &["init", "-q", "--bare", "."]);
+
+    write(plugin_dir.join("plugin.php"), "<?php\n/**\n * Plugin Name: Test Plugin\n * Version: 0.1.2\n */\n").unwrap();
+    write(plugin_dir.join("readme.txt"), "=== Test Plugin ===\n\n== Changelog ==\n\n= 0.2.0 =\n* Mirror support\n\n= 0.1.2 =\n* First release\n").unwrap();
+
+    // the pre-command stands in for the WP-CLI upgrade
+    write_test_config(&project_dir, "mirrors_dir = \"mirrors\"\n", &format!("{}\
+        pre_cmds = [\"sed -i 's/0.1.2/0.2.0/' plugin.php && echo new > new.txt\"]\n",
+        get_test_plugin(&remote_dir.display().to_string())));
+
+    let config = get_test_config(&project_dir);
+    let plugin = get_managed_plugins(&config).remove(0);
+
+    let outcome = Pipeline::new(&config, &plugin, &config.get_backups_dir()).unwrap().run();
+
+    let live_git_exists = plugin_dir.join(".git").exists();
+    let live_composerjson_exists = plugin_dir.join("composer.json").exists();
+    let mirror_new_file = read_to_string(mirror_dir.join("new.txt")).unwrap();
+    let mirror_composerjson = read_to_string(mirror_dir.join("composer.json")).unwrap();
+    let mirror_tags = git(&mirror_dir, &["tag", "--list"]);
+    let mirror_head = git(&mirror_dir, &["rev-parse", "HEAD"]);
+    let commit_message = git(&mirror_dir, &["log", "-1", "--format=%B"]);
+    let tag_message = git(&mirror_dir, &["for-each-ref", "refs/tags/0.2.0", "--format=%(contents)"]);
+    let remote_head = git(&remote_dir, &["rev-parse", "refs/heads/master"]);
+
+    // roll back to the initial commit of the mirror
+    git(&mirror_dir, &["tag", "0.1.2", "HEAD~1"]);
+
+    let rolled_back = rollback(get_test_config(&project_dir), "test-plugin", None, false);
+    let live_index = read_to_string(plugin_dir.join("plugin.php")).unwrap();
+    let live_new_file_exists = plugin_dir.join("new.txt").exists();
+    let live_git_exists_after_rollback = plugin_dir.join(".git").exists();
+    let rollback_message = git(&mirror_dir, &["log", "-1", "--format=%s"]);
+
+    remove_dir_all(&project_dir).unwrap();
+
+    assert_eq!(
+        Ok(UpgradeOutcome::Upgraded { from: "0.1.2".to_string(), to: "0.2.0".to_string() }),
+        outcome.map_err(|e| e.to_string())
+    );
+    assert!(!live_git_exists);
+    assert!(!live_composerjson_exists);
+    assert_eq!("new\n", mirror_new_file);
+    assert!(mirror_composerjson.contains("my-agency/test-plugin"));
+    assert_eq!("0.2.0", mirror_tags);
+    assert_eq!(mirror_head, remote_head);
+    assert_eq!(
+        "Update test-plugin from 0.1.2 to 0.2.0\n\n\
+        3 files changed: 2 added, 1 modified, 0 deleted\n\n\
+        modified: plugin.php\n\
+        added: composer.json\n\
+        added: new.txt",
+        commit_message
+    );
+    assert_eq!("test-plugin 0.2.0\n\n* Mirror support", tag_message);
+
+    assert!(rolled_back.is_ok());
+    assert!(live_index.contains("Version: 0.1.2"));
+    assert!(!live_new_file_exists);
+    assert!(!live_git_exists_after_rollback);
+    assert_eq!("Roll back test-plugin to 0.1.2", rollback_message);
+}
+
+#[test]
+fn test_failed_mirrored_upgrades_are_reset() {
+    let project_dir = get_test_dir("mirror-post-cmds-test");
+    let plugin_dir = project_dir.join("test-plugin");
+    let mirror_dir = project_dir.join("mirrors/test-plugin");
+    let remote_dir = project_dir.join("remote.git");
+
+    create_dir_all(&plugin_dir).unwrap();
+    create_dir_all(&remote_dir).unwrap();
+    git(&remote_dir, &["init", "-q", "--bare", "."]);
+
+    write(plugin_dir.join("plugin.php"), "<?php\n/**\n * Plugin Name: Test Plugin\n * Version: 0.1.2\n */\n").unwrap();
+
+    // the post-command fails until the release is marked as ok
+    write_test_config(&project_dir, "mirrors_dir = \"mirrors\"\n", &format!("{}\
+        pre_cmds = [\"sed -i 's/0.1.2/0.2.0/' plugin.php\"]\n\
+        post_cmds = [\"test -e ../release-ok\"]\n",
+        get_test_plugin(&remote_dir.display().to_string())));
+
+    let run_pipeline = || {
+        let config = get_test_config(&project_dir);
+        let plugin = get_managed_plugins(&config).remove(0);
+
+        Pipeline::new(&config, &plugin, &config.get_backups_dir()).unwrap().run()
+    };
+
+    let failed = run_pipeline();
+    let mirror_log = git(&mirror_dir, &["log", "--format=%s"]);
+    let mirror_tags = git(&mirror_dir, &["tag", "--list"]);
+    let mirror_changes = git(&mirror_dir, &["status", "--porcelain"]);
+    let live_index = read_to_string(plugin_dir.join("plugin.php")).unwrap();
+
+    write(project_dir.join("release-ok"), "").unwrap();
+
+    let rerun = run_pipeline();
+    let tagged_index = git(&mirror_dir, &["show", "0.2.0:plugin.php"]);
+    let remote_tags = git(&remote_dir, &["tag", "--list"]);
+
+    remove_dir_all(&project_dir).unwrap();
+
+    assert!(failed.is_err());
+    assert_eq!("Automated commit by wppr", mirror_log);
+    assert_eq!("", mirror_tags);
+    assert_eq!("", mirror_changes);
+    assert!(live_index.contains("Version: 0.1.2"));
+
+    assert!(rerun.is_ok());
+    assert!(tagged_index.contains("Version: 0.2.0"));
+    assert_eq!("0.2.0", remote_tags);
+}
@@ -0,0 +1,99 @@
+extern crate wppr;
+
+use std::fs::{create_dir_all, read_to_string, remove_dir_all, write};
+use std::path::Path;
+
+use wppr::commands::get_managed_plugins;
+use wppr::pipeline::{Pipeline, UpgradeOutcome};
+
+#[path = "./testfns.rs"]
+mod testfns;
+
+use testfns::{get_test_config, get_test_dir, get_test_plugin, git, write_test_config};
+
+fn run_pipeline(project_dir: &Path) -> Result<UpgradeOutcome, String> {
+    let config = get_test_config(project_dir);
+    let plugin = get_managed_plugins(&config).remove(0);
+
+    Pipeline::new(&config, &plugin, &config.get_backups_dir())
+        .unwrap()
+        .run()
+        .map_err(|e| e.to_string())
+}
+
+#[test]
+fn test_failed_post_cmds_do_not_leave_tags_behind() {
+    let project_dir = get_test_dir("pipeline-post-cmds-test");
+    let plugin_dir = project_dir.join("test-plugin");
+    let remote_dir = project_dir.join("remote.git");
+
+    create_dir_all(&plugin_dir).unwrap();
+    create_dir_all(&remote_dir).unwrap();
+    git(&remote_dir, &["init", "-q", "--bare", "."]);
+
+    write(plugin_dir.join("plugin.php"), "<?php\n/**\n * Plugin Name: Test Plugin\n * Version: 0.1.2\n */\n").unwrap();
+
+    // the pre-command stands in for the WP-CLI upgrade, the post-command
+    // fails until the release is marked as ok
+    write_test_config(&project_dir, "", &format!("{}\
+        pre_cmds = [\"sed -i 's/0.1.2/0.2.0/' plugin.php\"]\n\
+        post_cmds = [\"test -e ../release-ok\"]\n",
+        get_test_plugin(&remote_dir.display().to_string())));
+
+    let failed = run_pipeline(&project_dir);
+    let index_after_failure = read_to_string(plugin_dir.join("plugin.php")).unwrap();
+    let tags_after_failure = git(&plugin_dir, &["tag", "--list"]);
+    let commits_after_failure = git(&plugin_dir, &["rev-list", "--count", "HEAD"]);
+
+    write(project_dir.join("release-ok"), "").unwrap();
+
+    let rerun = run_pipeline(&project_dir);
+    let tags = git(&plugin_dir, &["tag", "--list"]);
+    let tagged_index = git(&plugin_dir, &["show", "0.2.0:plugin.php"]);
+    let remote_tags = git(&remote_dir, &["tag", "--list"]);
+
+    remove_dir_all(&project_dir).unwrap();
+
+    assert!(failed.is_err());
+    assert!(index_after_failure.contains("Version: 0.1.2"));
+    assert_eq!("", tags_after_failure);
+    assert_eq!("1", commits_after_failure);
+
+    assert_eq!(
+        Ok(UpgradeOutcome::Upgraded { from: "0.1.2".to_string(), to: "0.2.0".to_string() }),
+        rerun
+    );
+    assert_eq!("0.2.0", tags);
+    assert!(tagged_index.contains("Version: 0.2.0"));
+    assert_eq!("0.2.0", remote_tags);
+}
+
+#[test]
+fn test_failed_upgrade_commits_are_undone() {
+    let project_dir = get_test_dir("pipeline-commit-test");
+    let plugin_dir = project_dir.join("test-plugin");
+    let mirror_dir = project_dir.join("mirrors/test-plugin");
+
+    create_dir_all(&plugin_dir).unwrap();
+
+    write(plugin_dir.join("plugin.php"), "<?php\n/**\n * Plugin Name: Test Plugin\n * Version: 0.1.2\n */\n").unwrap();
+
+    // the pre-command upgrades the plugin and makes committing it fail
+    write_test_config(&project_dir, "mirrors_dir = \"mirrors\"\n", &format!("{}\
+        pre_cmds = [\"sed -i 's/0.1.2/0.2.0/' plugin.php && printf 'exit 1' > ../mirrors/test-plugin/.git/hooks/pre-commit && chmod +x ../mirrors/test-plugin/.git/hooks/pre-commit\"]\n",
+        get_test_plugin("git@example.com:test-plugin.git")));
+
+    let failed = run_pipeline(&project_dir);
+    let live_index = read_to_string(plugin_dir.join("plugin.php")).unwrap();
+    let mirror_index = read_to_string(mirror_dir.join("plugin.php")).unwrap();
+    let mirror_log = git(&mirror_dir, &["log", "--format=%s"]);
+    let mirror_changes = git(&mirror_dir, &["status", "--porcelain"]);
+
+    remove_dir_all(&project_dir).unwrap();
+
+    assert!(failed.unwrap_err().contains("commit"));
+    assert!(live_index.contains("Version: 0.1.2"));
+    assert!(mirror_index.contains("Version: 0.1.2"));
+    assert_eq!("Automated commit by wppr", mirror_log);
+    assert_eq!("", mirror_changes);
+}
@@ -0,0 +1,114 @@
+extern crate wppr;
+
+use std::fs::{read, read_to_string, remove_dir_all, write};
+use std::path::{Path, PathBuf};
+
+use wppr::artifacts::{get_checksum_path, sha256_file};
+use wppr::repository::*;
+use wppr::wordpress::Plugin;
+
+#[path = "./testfns.rs"]
+mod testfns;
+
+use testfns::{get_test_config, git, write_plugin_version};
+
+fn write_plugin_release(dir: &Path, version: &str) {
+    write_plugin_version(dir, version);
+    write(
+        dir.join("composer.json"),
+        "{\"name\": \"my-agency/my-plugin\", \"type\": \"wordpress-plugin\", \"license\": \"MIT\"}",
+    ).unwrap();
+}
+
+fn setup_test_project(name: &str) -> PathBuf {
+    let project_dir = testfns::setup_test_project(name, "\n\
+        [repository]\n\
+        url = \"https://composer.example.com/\"\n\
+        artifacts_dir = \"artifacts\"\n");
+    let plugin_dir = project_dir.join("my-plugin");
+
+    write_plugin_release(&plugin_dir, "1.0.0");
+    git(&plugin_dir, &["add", "."]);
+    git(&plugin_dir, &["commit", "-q", "-m", "First"]);
+    git(&plugin_dir, &["tag", "1.0.0"]);
+
+    write_plugin_release(&plugin_dir, "v1.1-beta1");
+    git(&plugin_dir, &["commit", "-q", "-a", "-m", "Second"]);
+    git(&plugin_dir, &["tag", "-a", "-m", "Beta", "v1.1-beta1"]);
+    git(&plugin_dir, &["tag", "not-a-version"]);
+
+    project_dir
+}
+
+#[test]
+fn test_plugin_tags_are_exported_as_package_versions() {
+    let project_dir = setup_test_project("repository-test");
+
+    let config = get_test_config(&project_dir);
+    let plugin = Plugin::from_config(config.plugins[0].clone(), &project_dir);
+
+    let package = get_package_versions(&config, &plugin);
+    let packages_json = build_packages_json(&[package.as_ref().unwrap().clone()]);
+
+    remove_dir_all(&project_dir).unwrap();
+
+    let package = package.unwrap();
+
+    assert_eq!(vec!["not-a-version".to_string()], package.skipped_tags);
+    assert_eq!(2, package.versions.len());
+
+    let stable = &packages_json["packages"]["my-agency/my-plugin"]["1.0.0"];
+    let beta = &packages_json["packages"]["my-agency/my-plugin"]["v1.1-beta1"];
+
+    assert_eq!("my-agency/my-plugin", stable["name"]);
+    assert_eq!("1.0.0", stable["version"]);
+    assert_eq!("1.0.0.0", stable["version_normalized"]);
+    assert_eq!("MIT", stable["license"]);
+    assert_eq!("git", stable["source"]["type"]);
+    assert_eq!("git@example.com:my-agency/my-plugin.git", stable["source"]["url"]);
+    assert_eq!(40, stable["source"]["reference"].as_str().unwrap().len());
+    assert!(stable["time"].is_string());
+
+    assert_eq!("1.1.0.0-beta1", beta["version_normalized"]);
+    assert_ne!(stable["source"]["reference"], beta["source"]["reference"]);
+}
+
+#[test]
+fn test_dist_archives_are_added_to_package_versions() {
+    let project_dir = setup_test_project("dist-test");
+    let export_dir = project_dir.join("export");
+
+    let config = get_test_config(&project_dir);
+    let plugin = Plugin::from_config(config.plugins[0].clone(), &project_dir);
+    let artifacts_dir = config.get_artifacts_dir().unwrap();
+
+    let mut package = get_package_versions(&config, &plugin).unwrap();
+    let result = add_dist_entries(&config, &plugin, &mut package, &artifacts_dir, &export_dir);
+
+    let archive = artifacts_dir.join("my-agency/my-plugin/my-plugin-1.0.0.zip");
+    let exported_archive = export_dir.join("dist/my-agency/my-plugin/my-plugin-1.0.0.zip");
+
+    let checksum = sha256_file(&archive).unwrap();
+    let checksum_file = read_to_string(get_checksum_path(&archive)).unwrap();
+    let archive_contents = read(&archive).unwrap();
+    let exported_contents = read(&exported_archive).unwrap();
+
+    // archives are deterministic, rebuilding produces an identical archive
+    remove_dir_all(&artifacts_dir).unwrap();
+    add_dist_entries(&config, &plugin, &mut package, &artifacts_dir, &export_dir).unwrap();
+    let rebuilt_contents = read(&archive).unwrap();
+
+    remove_dir_all(&project_dir).unwrap();
+
+    assert!(result.is_ok());
+    assert_eq!(format!("{}  my-plugin-1.0.0.zip\n", checksum), checksum_file);
+    assert_eq!(archive_contents, exported_contents);
+    assert_eq!(archive_contents, rebuilt_contents);
+
+    let dist = &package.versions["1.0.0"]["dist"];
+
+    assert_eq!("zip", dist["type"]);
+    assert_eq!("https://composer.example.com/dist/my-agency/my-plugin/my-plugin-1.0.0.zip", dist["url"]);
+    assert_eq!(package.versions["1.0.0"]["source"]["reference"], dist["reference"]);
+    assert!(package.versions["v1.1-beta1"]["dist"]["url"].as_str().unwrap().ends_with("my-plugin-v1.1-beta1.zip"));
+}
@@ -0,0 +1,64 @@
+extern crate wppr;
+
+use std::fs::{read_to_string, remove_dir_all};
+use std::path::{Path, PathBuf};
+
+use wppr::commands::rollback;
+use wppr::config::PluginHold;
+
+#[path = "./testfns.rs"]
+mod testfns;
+
+use testfns::{get_test_config, git, write_plugin_version};
+
+fn release_plugin_version(dir: &Path, version: &str, tag: &str) {
+    write_plugin_version(dir, version);
+    git(dir, &["add", "."]);
+    git(dir, &["commit", "-q", "-m", version]);
+    git(dir, &["tag", tag]);
+}
+
+fn setup_test_project(name: &str) -> PathBuf {
+    let project_dir = testfns::setup_test_project(name, "");
+    let plugin_dir = project_dir.join("my-plugin");
+
+    release_plugin_version(&plugin_dir, "1.0.0", "1.0.0");
+    release_plugin_version(&plugin_dir, "1.1.0", "1.1.0");
+
+    // a tag which does not match the plugin header
+    release_plugin_version(&plugin_dir, "1.2.0", "2.0.0");
+    git(&plugin_dir, &["tag", "1.2.0"]);
+
+    project_dir
+}
+
+#[test]
+fn test_plugin_is_rolled_back_to_previous_version() {
+    let project_dir = setup_test_project("rollback-test");
+    let plugin_dir = project_dir.join("my-plugin");
+
+    let mismatching = rollback(get_test_config(&project_dir), "my-plugin", Some("2.0.0"), false);
+    let index_after_mismatch = read_to_string(plugin_dir.join("my-plugin.php")).unwrap();
+
+    let missing = rollback(get_test_config(&project_dir), "my-plugin", Some("0.9"), false);
+
+    let result = rollback(get_test_config(&project_dir), "my-plugin", None, true);
+    let index_after_rollback = read_to_string(plugin_dir.join("my-plugin.php")).unwrap();
+    let commit_message = git(&plugin_dir, &["log", "-1", "--format=%s"]);
+    let branch = git(&plugin_dir, &["rev-parse", "--abbrev-ref", "HEAD"]);
+    let config_contents = read_to_string(project_dir.join("wppr.toml")).unwrap();
+    let config = get_test_config(&project_dir);
+
+    remove_dir_all(&project_dir).unwrap();
+
+    assert!(mismatching.is_err());
+    assert!(index_after_mismatch.contains("Version: 1.2.0"));
+    assert!(missing.is_err());
+
+    assert!(result.is_ok());
+    assert!(index_after_rollback.contains("Version: 1.1.0"));
+    assert_eq!("Roll back my-plugin to 1.1.0", commit_message);
+    assert_ne!("HEAD", branch);
+    assert!(config_contents.contains("# managed plugins\n"));
+    assert_eq!(Some(PluginHold::Version("1.1.0".to_string())), config.plugins[0].hold);
+}
@@ -0,0 +1,94 @@
+extern crate wppr;
+
+use std::env;
+use std::fs::{create_dir_all, remove_dir_all, write};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::thread;
+
+use wppr::config::BasicAuthConfig;
+use wppr::server::RepositoryServer;
+
+fn setup_repository_dir(name: &str) -> PathBuf {
+    let root = env::temp_dir().join(format!("wppr-{}-{}", name, std::process::id()));
+
+    create_dir_all(root.join("dist/my-agency/my-plugin")).unwrap();
+    write(root.join("packages.json"), "{\"packages\": {}}").unwrap();
+    write(root.join("dist/my-agency/my-plugin/my-plugin-1.0.0.zip"), "zip").unwrap();
+    write(root.join("secret.txt"), "secret").unwrap();
+
+    root
+}
+
+fn get_test_server(root: PathBuf) -> RepositoryServer {
+    let auth = BasicAuthConfig {
+        username: "composer".to_string(),
+        password: "s3cret".to_string(),
+    };
+
+    RepositoryServer::new(root, Some(&auth))
+}
+
+fn request(addr: SocketAddr, path: &str, authorization: Option<&str>) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let auth_header = authorization.map(|a| format!("Authorization: {}\r\n", a)).unwrap_or_default();
+
+    write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n{}Connection: close\r\n\r\n", path, auth_header).unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    response
+}
+
+#[test]
+fn test_request_paths_are_resolved_inside_repository() {
+    let root = setup_repository_dir("server-paths-test");
+    let server = get_test_server(root.clone());
+
+    let packages = server.resolve_path("/packages.json");
+    let archive = server.resolve_path("/dist/my-agency/my-plugin/my-plugin-1.0.0.zip?ref=1");
+    let secret = server.resolve_path("/secret.txt");
+    let traversal = server.resolve_path("/dist/../secret.txt");
+    let missing = server.resolve_path("/dist/missing.zip");
+
+    remove_dir_all(&root).unwrap();
+
+    assert_eq!(Some(root.join("packages.json")), packages);
+    assert_eq!(Some(root.join("dist/my-agency/my-plugin/my-plugin-1.0.0.zip")), archive);
+    assert_eq!(None, secret);
+    assert_eq!(None, traversal);
+    assert_eq!(None, missing);
+}
+
+#[test]
+fn test_repository_is_served_with_basic_auth() {
+    let root = setup_repository_dir("server-auth-test");
+    let server = get_test_server(root.clone());
+    let http = RepositoryServer::bind("127.0.0.1:0").unwrap();
+    let addr = http.server_addr().to_ip().unwrap();
+
+    thread::spawn(move || server.serve(&http));
+
+    // `composer:s3cret` base64 encoded
+    let credentials = "Basic Y29tcG9zZXI6czNjcmV0";
+
+    let unauthorized = request(addr, "/packages.json", None);
+    let wrong_password = request(addr, "/packages.json", Some("Basic Y29tcG9zZXI6d3Jvbmc="));
+    let packages = request(addr, "/packages.json", Some(credentials));
+    let archive = request(addr, "/dist/my-agency/my-plugin/my-plugin-1.0.0.zip", Some(credentials));
+    let missing = request(addr, "/secret.txt", Some(credentials));
+
+    remove_dir_all(&root).unwrap();
+
+    assert!(unauthorized.starts_with("HTTP/1.1 401"));
+    assert!(unauthorized.contains("WWW-Authenticate: Basic"));
+    assert!(wrong_password.starts_with("HTTP/1.1 401"));
+    assert!(packages.starts_with("HTTP/1.1 200"));
+    assert!(packages.contains("Content-Type: application/json"));
+    assert!(packages.ends_with("{\"packages\": {}}"));
+    assert!(archive.starts_with("HTTP/1.1 200"));
+    assert!(archive.contains("Content-Type: application/zip"));
+    assert!(missing.starts_with("HTTP/1.1 404"));
+}
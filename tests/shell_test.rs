@@ -0,0 +1,96 @@
+extern crate wppr;
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use wppr::error::Error;
+use wppr::shell::Shell;
+
+#[path = "./testfns.rs"]
+mod testfns;
+
+fn get_test_shell(timeout: u64) -> Shell {
+    Shell::new("sh".to_string(), testfns::get_tests_dir("data"), timeout)
+}
+
+#[test]
+fn test_shell_commands_output_is_captured() {
+    let shell = get_test_shell(10);
+
+    let output = shell.run("echo 'hello world'; echo 'oops' >&2").unwrap();
+
+    assert!(output.success());
+    assert_eq!("hello world\n", output.stdout);
+    assert_eq!("oops\n", output.stderr);
+}
+
+#[test]
+fn test_shell_commands_run_in_working_directory_with_env() {
+    let mut shell = get_test_shell(10);
+
+    shell.set_env("WPPR_PLUGIN_NICENAME", "test-plugin/plugin.php");
+
+    let output = shell.run("pwd; echo $WPPR_PLUGIN_NICENAME").unwrap();
+    let mut lines = output.stdout.lines();
+
+    assert_eq!(
+        PathBuf::from(lines.next().unwrap()),
+        testfns::get_tests_dir("data")
+    );
+    assert_eq!("test-plugin/plugin.php", lines.next().unwrap());
+}
+
+#[test]
+fn test_failing_shell_commands_are_reported() {
+    let shell = get_test_shell(10);
+
+    let output = shell.run("echo 'broken' >&2; exit 3").unwrap();
+
+    assert!(!output.success());
+    assert_eq!(Some(3), output.exit_code);
+    assert!(format!("{}", output.error().unwrap()).contains("broken"));
+}
+
+#[test]
+fn test_shell_commands_time_out() {
+    let shell = get_test_shell(1);
+
+    let output = shell.run("sleep 5; echo 'done'").unwrap();
+
+    assert!(output.timed_out);
+    assert!(!output.success());
+    assert_eq!("", output.stdout);
+}
+
+#[test]
+fn test_timed_out_shell_commands_children_are_killed() {
+    let shell = get_test_shell(1);
+    let started = Instant::now();
+
+    // the background child keeps the output pipes open unless it is killed
+    let output = shell.run("sleep 5 & wait").unwrap();
+
+    assert!(output.timed_out);
+    assert!(started.elapsed() < Duration::from_secs(4));
+}
+
+#[test]
+fn test_background_children_of_shell_commands_are_killed() {
+    let shell = get_test_shell(10);
+    let started = Instant::now();
+
+    let output = shell.run("sleep 100 & echo 'started'").unwrap();
+
+    assert!(output.success());
+    assert_eq!("started\n", output.stdout);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn test_invalid_shell_cannot_run_commands() {
+    let shell = Shell::new("/does/not/exist".to_string(), PathBuf::from("/"), 10);
+
+    match shell.run("true") {
+        Err(Error::MissingBinary { bin, .. }) => assert_eq!("/does/not/exist", bin),
+        _ => panic!("Expected a missing binary error")
+    };
+}
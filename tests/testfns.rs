@@ -3,11 +3,19 @@
 //! Test functions, helpers, and utils.
 
 extern crate fs_extra;
+extern crate wppr;
 
-use std::{process, env, path::PathBuf};
+use std::{process, env, fs, path::{Path, PathBuf}};
 
 use self::fs_extra::dir;
 
+use self::wppr::config::{RuntimeConfig, TomlConfig};
+
+/// Plugin table options for the `my-plugin` test plugin.
+pub const MY_PLUGIN: &str = "index_path = \"my-plugin/my-plugin.php\"\n\
+    package_name = \"my-agency/my-plugin\"\n\
+    remote_repository = \"git@example.com:my-agency/my-plugin.git\"\n";
+
 /// Get the working directory where tests have been run from.
 pub fn get_cwd() -> PathBuf {
     PathBuf::from(env::current_dir().unwrap().to_str().unwrap())
@@ -97,3 +105,79 @@ pub fn discard_test_dummy_project() {
         panic!("Could not remove test project!");
     }
 }
+
+/// Run git in a directory, returning its trimmed output.
+pub fn git(dir: &Path, args: &[&str]) -> String {
+    let output = process::Command::new("git").current_dir(dir).args(args).output().unwrap();
+
+    assert!(output.status.success());
+
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// Get an empty temporary directory for a single test.
+pub fn get_test_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("wppr-{}-{}", name, process::id()));
+
+    fs::create_dir_all(&dir).unwrap();
+
+    dir
+}
+
+/// Get the plugin table options for the `test-plugin` test plugin.
+pub fn get_test_plugin(remote_repository: &str) -> String {
+    format!("index_path = \"test-plugin/plugin.php\"\n\
+        package_name = \"my-agency/test-plugin\"\n\
+        remote_repository = \"{}\"\n", remote_repository)
+}
+
+/// Write a `wppr.toml` managing a single plugin into a test project.
+/// `git_options` are added to the `[git]` table, `plugin` contains the
+/// options of the plugin table and may be followed by other tables.
+pub fn write_test_config(project_dir: &Path, git_options: &str, plugin: &str) {
+    fs::write(project_dir.join("wppr.toml"), format!("[binaries]\n\
+        git = \"git\"\n\
+        wpcli = \"/bin/true\"\n\
+        \n\
+        [git]\n\
+        user_name = \"test person\"\n\
+        user_email = \"testperson@example.com\"\n\
+        force_push = false\n\
+        {}\
+        \n\
+        # managed plugins\n\
+        [[plugins]]\n\
+        {}", git_options, plugin)).unwrap();
+}
+
+/// Load the runtime config of a test project.
+pub fn get_test_config(project_dir: &Path) -> RuntimeConfig {
+    let toml_config = TomlConfig::load_from_file(project_dir.join("wppr.toml")).unwrap();
+
+    RuntimeConfig::from_toml_config(toml_config).unwrap()
+}
+
+/// Set up a test project with the `my-plugin` plugin in an initialized but
+/// empty git repository. `tables` are added to the project config.
+pub fn setup_test_project(name: &str, tables: &str) -> PathBuf {
+    let project_dir = get_test_dir(name);
+    let plugin_dir = project_dir.join("my-plugin");
+
+    fs::create_dir_all(&plugin_dir).unwrap();
+
+    write_test_config(&project_dir, "", &format!("{}{}", MY_PLUGIN, tables));
+
+    git(&plugin_dir, &["init", "-q", "."]);
+    git(&plugin_dir, &["config", "user.name", "test person"]);
+    git(&plugin_dir, &["config", "user.email", "testperson@example.com"]);
+
+    project_dir
+}
+
+/// Write a plugin index file with the given version.
+pub fn write_plugin_version(dir: &Path, version: &str) {
+    fs::write(
+        dir.join("my-plugin.php"),
+        format!("<?php\n/**\n * Plugin Name: My Plugin\n * Version: {}\n */\n", version),
+    ).unwrap();
+}
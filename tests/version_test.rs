@@ -0,0 +1,51 @@
+extern crate wppr;
+
+use wppr::version::{Stability, Version};
+
+fn v(version: &str) -> Version {
+    Version::parse(version).unwrap()
+}
+
+#[test]
+fn test_versions_are_parsed_and_normalized() {
+    assert_eq!("5.12.0.0", v("5.12").normalized());
+    assert_eq!("2.7.1.3", v("2.7.1.3").normalized());
+    assert_eq!("1.0.0.0-beta2", v("1.0.0-beta2").normalized());
+    assert_eq!("3.1.0.0", v("v3.1").normalized());
+    assert_eq!("1.0.0.0-RC1", v("1.0.0rc1").normalized());
+    assert_eq!("1.2.0.0-patch1", v("1.2.p1").normalized());
+    assert_eq!("2.0.0.0-dev", v("2.0-dev").normalized());
+    assert_eq!(Stability::Beta, v("1.0.0.b2").get_stability());
+}
+
+#[test]
+fn test_versions_are_formatted_for_tags() {
+    assert_eq!("5.12", v("5.12").to_tag());
+    assert_eq!("3.1", v("v3.1").to_tag());
+    assert_eq!("1.0.0-beta2", v("1.0.0.b2").to_tag());
+    assert_eq!("4.0-alpha1.2", v("4.0-alpha.1.2").to_tag());
+    assert_eq!("v3.1", v(" v3.1 ").to_string());
+}
+
+#[test]
+fn test_invalid_versions_are_errors() {
+    assert!(Version::parse("").is_err());
+    assert!(Version::parse("latest").is_err());
+    assert!(Version::parse("1.2.3.4.5").is_err());
+    assert!(Version::parse("1.2.3 beta").is_err());
+    assert!(Version::parse("99999999999999999999999.0").is_err());
+}
+
+#[test]
+fn test_versions_are_compared() {
+    assert!(v("5.12") > v("5.9"));
+    assert!(v("2.7.1.3") > v("2.7.1"));
+    assert!(v("1.0.0-beta2") < v("1.0.0"));
+    assert!(v("1.0.0-beta2") > v("1.0.0-beta1"));
+    assert!(v("1.0.0-alpha3") < v("1.0.0-beta1"));
+    assert!(v("1.0.0-rc1") < v("1.0.0"));
+    assert!(v("1.0.0-patch1") > v("1.0.0"));
+    assert!(v("1.0-dev") < v("1.0-alpha1"));
+    assert!(v("1.0-beta1-dev") < v("1.0-beta1"));
+    assert_eq!(v("v3.1"), v("3.1.0"));
+}
@@ -2,7 +2,8 @@ extern crate wppr;
 
 use std::path::PathBuf;
 use wppr::config;
-use wppr::wordpress::{get_plugin_version, Plugin};
+use wppr::policy::UpgradePolicy;
+use wppr::wordpress::{get_plugin_version, Plugin, PluginHeader};
 
 #[path = "./testfns.rs"]
 mod testfns;
@@ -19,7 +20,14 @@ fn test_plugin_versions_can_be_read() {
         remote_repository: "".to_string(),
         installed_version: None,
         nicename: None,
-        pre_cmds: Vec::new()
+        header: None,
+        pre_cmds: Vec::new(),
+        post_cmds: Vec::new(),
+        cmds_timeout: 300,
+        composer_overrides: None,
+        composer_strategy: config::ComposerStrategy::Keep,
+        hold: None,
+        policy: UpgradePolicy::default()
     };
 
     let version = get_plugin_version(&plugin).ok().unwrap();
@@ -33,10 +41,60 @@ fn test_plugin_can_be_created_from_config() {
         index_path: get_test_plugin_index().to_str().unwrap().to_string(),
         package_name: "".to_string(),
         remote_repository: "".to_string(),
-        pre_cmds: None
+        pre_cmds: None,
+        post_cmds: None,
+        cmds_timeout: None,
+        composer: None,
+        composer_strategy: None,
+        hold: None,
+        allow: None,
+        max_version: None
     };
 
     let plugin = Plugin::from_config(pluginconfig, &PathBuf::from(""));
 
     assert_eq!(plugin.index_path, get_test_plugin_index());
 }
+
+#[test]
+fn test_plugin_headers_can_be_read() {
+    let header = PluginHeader::from_file(&testfns::get_tests_dir("data/plugins/header-plugin/header-plugin.php")).unwrap();
+
+    assert_eq!(Some("Header Plugin".to_string()), header.name);
+    assert_eq!(Some("https://example.com/header-plugin".to_string()), header.plugin_uri);
+    assert_eq!(Some("Plugin with a complete header.".to_string()), header.description);
+    assert_eq!(Some("2.7.1.3".to_string()), header.version);
+    assert_eq!(Some("5.2".to_string()), header.requires_wp);
+    assert_eq!(Some("7.2".to_string()), header.requires_php);
+    assert_eq!(Some("Jane Doe".to_string()), header.author);
+    assert_eq!(Some("https://example.com".to_string()), header.author_uri);
+    assert_eq!(Some("GPL v2 or later".to_string()), header.license);
+    assert_eq!(Some("header-plugin".to_string()), header.text_domain);
+    assert_eq!(None, header.domain_path);
+    assert!(header.network);
+    assert_eq!(Some("https://example.com/updates".to_string()), header.update_uri);
+    assert_eq!(vec!["woocommerce".to_string(), "akismet".to_string()], header.requires_plugins);
+}
+
+#[test]
+fn test_missing_plugin_version_is_an_error() {
+    let pluginconfig = config::PluginConfig {
+        index_path: testfns::get_tests_dir("data/plugins/no-version/plugin.php").to_str().unwrap().to_string(),
+        package_name: "".to_string(),
+        remote_repository: "".to_string(),
+        pre_cmds: None,
+        post_cmds: None,
+        cmds_timeout: None,
+        composer: None,
+        composer_strategy: None,
+        hold: None,
+        allow: None,
+        max_version: None
+    };
+
+    let plugin = Plugin::from_config(pluginconfig, &PathBuf::from(""));
+
+    assert!(get_plugin_version(&plugin).is_err());
+    assert_eq!(None, plugin.installed_version);
+    assert_eq!(Some("No version here".to_string()), plugin.header.unwrap().name);
+}